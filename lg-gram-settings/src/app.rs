@@ -1,8 +1,11 @@
+use std::ops::ControlFlow;
+
 use gtk::{gio, glib};
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 
 use crate::window::MainWindow;
+use crate::lg_gram::gram;
 
 //------------------------------------------------------------------------------
 // MODULE: Application
@@ -33,11 +36,25 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
-            self.obj().setup_actions();
+            let obj = self.obj();
+
+            obj.setup_options();
+            obj.setup_actions();
         }
     }
 
     impl ApplicationImpl for Application {
+        //---------------------------------------
+        // Handle local options handler
+        //---------------------------------------
+        fn handle_local_options(&self, options: &glib::VariantDict) -> ControlFlow<glib::ExitCode> {
+            if let Ok(Some(name)) = options.lookup::<String>("elevation") {
+                gram::select_elevation(&name);
+            }
+
+            self.parent_handle_local_options(options)
+        }
+
         //---------------------------------------
         // Activate handler
         //---------------------------------------
@@ -77,6 +94,21 @@ impl Application {
             .build()
     }
 
+    //---------------------------------------
+    // Setup options
+    //---------------------------------------
+    fn setup_options(&self) {
+        // Elevation method option
+        self.add_main_option(
+            "elevation",
+            glib::Char::from(b'e'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::String,
+            "Elevation method used to change settings (pkexec, run0 or sudo)",
+            Some("METHOD")
+        );
+    }
+
     //---------------------------------------
     // Setup actions
     //---------------------------------------
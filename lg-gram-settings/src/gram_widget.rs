@@ -1,4 +1,6 @@
 use std::cell::{Cell, RefCell, OnceCell};
+use std::collections::HashMap;
+use std::rc::Rc;
 
 use gtk::glib;
 use adw::subclass::prelude::*;
@@ -6,6 +8,46 @@ use adw::prelude::*;
 
 use crate::lg_gram::gram;
 
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const REVERT_DELAY: u32 = 2;
+
+//------------------------------------------------------------------------------
+// ENUM: ApplyMode
+//------------------------------------------------------------------------------
+#[derive(Default, Debug, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "ApplyMode")]
+pub enum ApplyMode {
+    #[default]
+    #[enum_value(name = "Persistent")]
+    Persistent,
+    #[enum_value(name = "Until Reboot")]
+    UntilReboot,
+    #[enum_value(name = "For 1 Hour")]
+    OneHour,
+    #[enum_value(name = "For 2 Hours")]
+    TwoHours,
+    #[enum_value(name = "For 4 Hours")]
+    FourHours
+}
+
+impl ApplyMode {
+    //---------------------------------------
+    // Apply function
+    //---------------------------------------
+    fn apply(self) -> gram::Apply {
+        match self {
+            Self::Persistent => gram::Apply::Persistent,
+            Self::UntilReboot => gram::Apply::UntilReboot,
+            Self::OneHour => gram::Apply::ForMinutes(60),
+            Self::TwoHours => gram::Apply::ForMinutes(120),
+            Self::FourHours => gram::Apply::ForMinutes(240)
+        }
+    }
+}
+
 //------------------------------------------------------------------------------
 // MODULE: GramWidget
 //------------------------------------------------------------------------------
@@ -21,6 +63,14 @@ mod imp {
     pub struct GramWidget {
         #[template_child]
         pub(super) icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub(super) runtime_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) managed_icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub(super) override_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub(super) override_label: TemplateChild<gtk::Label>,
 
         #[property(get, set, nullable)]
         icon_name: RefCell<Option<String>>,
@@ -28,11 +78,17 @@ mod imp {
         off_value: RefCell<Option<String>>,
         #[property(get, set, nullable, default = Some("1"), construct)]
         on_value: RefCell<Option<String>>,
+        #[property(get, set, builder(ApplyMode::default()))]
+        apply_mode: Cell<ApplyMode>,
 
-        pub(super) id: OnceCell<String>,
+        pub(super) feature: OnceCell<gram::Feature>,
+        pub(super) backend: OnceCell<Rc<dyn gram::Backend>>,
+        pub(super) persisted_value: Cell<Option<gram::Value>>,
+        pub(super) default_subtitle: OnceCell<glib::GString>,
+        pub(super) override_source: RefCell<Option<glib::SourceId>>,
 
         pub(super) selected_index: Cell<Option<u32>>,
-        pub(super) is_changing: Cell<bool>,
+        pub(super) selected_handler: OnceCell<glib::SignalHandlerId>,
     }
 
     //---------------------------------------
@@ -83,40 +139,57 @@ mod imp {
         //---------------------------------------
         fn install_actions(klass: &mut <Self as ObjectSubclass>::Class) {
             // Gram set feature action
-            klass.install_action_async("gram.set-feature", Some(glib::VariantTy::STRING),
+            klass.install_action_async("gram.set-feature", Some(glib::VariantTy::INT32),
                 async |widget, _, param| {
                     let imp = widget.imp();
 
-                    if imp.is_changing.get() {
-                        imp.is_changing.set(false);
-                        return;
-                    }
-
-                    let Some(id) = widget.imp().id.get() else {
-                        widget.throw_error("ERROR: setting ID not initialized");
+                    let Some(&feature) = widget.imp().feature.get() else {
+                        widget.throw_error("ERROR: feature not initialized");
                         return
                     };
 
-                    let Some(value) = param.and_then(|param| param.get::<String>()) else {
+                    let value = param.and_then(|param| param.get::<i32>())
+                        .and_then(|raw| gram::Value::from_raw(feature, raw));
+
+                    let Some(value) = value else {
                         widget.throw_error("ERROR: failed to get variant value");
                         return
                     };
 
-                    if let Err(error) = gram::set_feature_async(id, &value).await {
-                        imp.is_changing.set(true);
+                    let apply = widget.apply_mode().apply();
 
+                    if let Err(error) = widget.backend().set_feature(value, apply).await {
                         if let Some(index) = imp.selected_index.get() {
-                            widget.set_selected(index);
+                            widget.select_silently(index);
                         } else {
                             widget.set_sensitive(false);
                         }
 
-                        widget.throw_error(&error);
+                        widget.handle_error(&error);
                     } else {
                         imp.selected_index.set(Some(widget.selected()));
+
+                        if apply == gram::Apply::Persistent {
+                            imp.persisted_value.set(Some(value));
+                        }
+
+                        widget.update_override();
+                        widget.update_runtime_label();
                     }
                 }
             );
+
+            // Gram revert feature action
+            klass.install_action_async("gram.revert-feature", None, async |widget, _, _| {
+                let Some(&feature) = widget.imp().feature.get() else {
+                    return
+                };
+
+                match widget.backend().revert_feature(feature).await {
+                    Ok(_) => widget.refresh(),
+                    Err(error) => widget.handle_error(&error)
+                }
+            });
         }
     }
 }
@@ -149,17 +222,41 @@ impl GramWidget {
     //---------------------------------------
     fn setup_signals(&self) {
         // Selected item property notify signal
-        self.connect_selected_item_notify(|widget| {
-            let id = widget.imp().id.get();
+        let handler = self.connect_selected_item_notify(|widget| {
+            let feature = widget.imp().feature.get();
 
-            if id.is_some() && let Some(item) = widget.selected_item()
+            if feature.is_some() && let Some(item) = widget.selected_item()
                 .and_downcast::<adw::EnumListItem>() {
-                    let variant = item.value().to_string().to_variant();
+                    let variant = item.value().to_variant();
 
                     widget.activate_action("gram.set-feature", Some(&variant)).unwrap();
                 }
 
         });
+
+        self.imp().selected_handler.set(handler).unwrap();
+    }
+
+    //---------------------------------------
+    // Select silently helper function
+    //---------------------------------------
+    fn select_silently(&self, index: u32) {
+        let imp = self.imp();
+
+        // Block selected item handler, so that value is not written back
+        if let Some(handler) = imp.selected_handler.get() {
+            self.block_signal(handler);
+            self.set_selected(index);
+            self.unblock_signal(handler);
+        }
+    }
+
+    //---------------------------------------
+    // Backend helper function
+    //---------------------------------------
+    fn backend(&self) -> Rc<dyn gram::Backend> {
+        self.imp().backend.get().cloned()
+            .expect("Failed to get backend")
     }
 
     //---------------------------------------
@@ -170,34 +267,450 @@ impl GramWidget {
     }
 
     //---------------------------------------
-    // Init function
+    // Handle error helper function
     //---------------------------------------
-    pub fn init(&self, id: &str, enum_type: glib::Type) {
-        let model = adw::EnumListModel::new(enum_type);
+    fn handle_error(&self, error: &gram::Error) {
+        match error {
+            // Selection is already restored, nothing to report
+            gram::Error::ElevationCancelled => {},
+            gram::Error::AttributeMissing(_) => {
+                self.set_subtitle("Not supported on this laptop");
+                self.set_sensitive(false);
+            },
+            gram::Error::ElevationUnavailable(message) => {
+                self.activate_action("win.show-elevation-error", Some(&message.to_variant())).unwrap();
+            },
+            gram::Error::WriterFailed(gram::WriterCategory::Policy, _) => {
+                // Policy changed since window was opened
+                self.activate_action("win.reload-policy", None).unwrap();
+                self.throw_error(&error.to_string());
+            },
+            _ => self.throw_error(&error.to_string())
+        }
+    }
 
-        self.set_model(Some(&model));
+    //---------------------------------------
+    // Update runtime label helper function
+    //---------------------------------------
+    fn update_runtime_label(&self) {
+        let imp = self.imp();
 
-        let active_index = gram::feature(id)
-            .and_then(|value| {
+        let persisted_item = self.model()
+            .and_downcast::<adw::EnumListModel>()
+            .zip(imp.persisted_value.get())
+            .and_then(|(model, value)| {
                 model.iter::<adw::EnumListItem>().flatten()
-                    .position(|item| Ok(item.value()) == value.parse::<i32>())
-                    .ok_or_else(|| String::from("unknown value"))
+                    .find(|item| item.value() == value.raw())
             });
 
-        match active_index {
-            Ok(index) => {
-                let imp = self.imp();
+        let selected_item = self.selected_item()
+            .and_downcast::<adw::EnumListItem>();
+
+        // Show label if live value differs from persisted value (and no override is active)
+        if imp.override_box.is_visible() {
+            imp.runtime_label.set_visible(false);
+            return
+        }
+
+        match (selected_item, persisted_item) {
+            (Some(selected_item), Some(persisted_item)) if selected_item.value() != persisted_item.value() => {
+                imp.runtime_label.set_tooltip_text(Some(&format!("Reverts to {} on reboot", persisted_item.name())));
+                imp.runtime_label.set_visible(true);
+            },
+            _ => {
+                imp.runtime_label.set_visible(false);
+            }
+        }
+    }
+
+    //---------------------------------------
+    // Update override helper function
+    //---------------------------------------
+    fn update_override(&self) {
+        let imp = self.imp();
+
+        if let Some(source) = imp.override_source.take() {
+            source.remove();
+        }
+
+        let expiry = imp.feature.get()
+            .and_then(|&feature| self.backend().feature_override(feature))
+            .map(|(_, expiry)| expiry);
+
+        let Some(expiry) = expiry else {
+            imp.override_box.set_visible(false);
+            return
+        };
+
+        if !self.update_override_label(expiry) {
+            return
+        }
+
+        // Update remaining time every second
+        let source = glib::timeout_add_seconds_local(1, glib::clone!(
+            #[weak(rename_to = widget)] self,
+            #[upgrade_or] glib::ControlFlow::Break,
+            move || {
+                if widget.update_override_label(expiry) {
+                    return glib::ControlFlow::Continue
+                }
+
+                widget.imp().override_source.take();
+
+                // Give revert timer time to restore value
+                glib::timeout_add_seconds_local_once(REVERT_DELAY, glib::clone!(
+                    #[weak] widget,
+                    move || {
+                        widget.refresh();
+                    }
+                ));
+
+                glib::ControlFlow::Break
+            }
+        ));
+
+        imp.override_source.replace(Some(source));
+    }
+
+    //---------------------------------------
+    // Update override label helper function
+    //---------------------------------------
+    fn update_override_label(&self, expiry: u64) -> bool {
+        let imp = self.imp();
+
+        let remaining = gram::override_remaining(expiry);
+
+        if remaining > 0 {
+            imp.override_label.set_label(&format!("{}:{:02}:{:02}", remaining / 3600, remaining % 3600 / 60, remaining % 60));
+        }
+
+        imp.override_box.set_visible(remaining > 0);
+
+        remaining > 0
+    }
+
+    //---------------------------------------
+    // Index of value helper function
+    //---------------------------------------
+    fn index_of(&self, value: gram::Value) -> Option<u32> {
+        self.model()
+            .and_downcast::<adw::EnumListModel>()?
+            .iter::<adw::EnumListItem>().flatten()
+            .position(|item| item.value() == value.raw())
+            .map(|index| index as u32)
+    }
+
+    //---------------------------------------
+    // Init function
+    //---------------------------------------
+    pub fn init(&self, feature: gram::Feature, backend: &Rc<dyn gram::Backend>, snapshot: &gram::Snapshot) {
+        self.imp().backend.get_or_init(|| Rc::clone(backend));
 
-                self.set_selected(index as u32);
+        let model = adw::EnumListModel::new(feature.enum_type());
 
-                imp.id.set(id.to_owned()).unwrap();
-                imp.selected_index.set(Some(index as u32));
+        self.set_model(Some(&model));
 
-                self.set_sensitive(true);
+        let value = match snapshot.reading(feature) {
+            gram::Reading::Value(value) => *value,
+            gram::Reading::Missing => {
+                self.handle_error(&gram::Error::AttributeMissing(feature));
+                return
             },
-            Err(error) => {
-                self.throw_error(&format!("Failed to read {id}: {error}"));
+            gram::Reading::Failed(error) => {
+                self.throw_error(error);
+                return
             }
+        };
+
+        let Some(index) = self.index_of(value) else {
+            self.handle_error(&gram::Error::UnexpectedValue(feature, value.to_string()));
+            return
+        };
+
+        let imp = self.imp();
+
+        self.set_selected(index);
+
+        imp.feature.set(feature).unwrap();
+        imp.selected_index.set(Some(index));
+
+        self.update_override();
+
+        self.set_sensitive(true);
+    }
+
+    //---------------------------------------
+    // Refresh function
+    //---------------------------------------
+    pub fn refresh(&self) {
+        let imp = self.imp();
+
+        let Some(&feature) = imp.feature.get() else {
+            return
+        };
+
+        match self.backend().feature(feature) {
+            Ok(value) => self.update_value(value),
+            Err(error) => self.handle_error(&error)
+        }
+    }
+
+    //---------------------------------------
+    // Update from snapshot function
+    //---------------------------------------
+    pub fn update_from_snapshot(&self, snapshot: &gram::Snapshot) {
+        let Some(&feature) = self.imp().feature.get() else {
+            return
+        };
+
+        match snapshot.reading(feature) {
+            gram::Reading::Value(value) => self.update_value(*value),
+            gram::Reading::Missing => self.handle_error(&gram::Error::AttributeMissing(feature)),
+            gram::Reading::Failed(error) => self.throw_error(error)
+        }
+    }
+
+    //---------------------------------------
+    // Update value function
+    //---------------------------------------
+    pub fn update_value(&self, value: gram::Value) {
+        let imp = self.imp();
+
+        if imp.feature.get() != Some(&value.feature()) {
+            return
+        }
+
+        let Some(index) = self.index_of(value) else {
+            self.handle_error(&gram::Error::UnexpectedValue(value.feature(), value.to_string()));
+            return
+        };
+
+        if index != self.selected() {
+            self.select_silently(index);
+        }
+
+        imp.selected_index.set(Some(index));
+
+        self.update_override();
+        self.update_runtime_label();
+    }
+
+    //---------------------------------------
+    // Set persisted values function
+    //---------------------------------------
+    pub fn set_persisted_values(&self, values: &HashMap<gram::Feature, gram::Value>) {
+        let imp = self.imp();
+
+        let Some(feature) = imp.feature.get() else {
+            return
+        };
+
+        imp.persisted_value.set(values.get(feature).copied());
+
+        self.update_runtime_label();
+    }
+
+    //---------------------------------------
+    // Set policy function
+    //---------------------------------------
+    pub fn set_policy(&self, rules: &HashMap<gram::Feature, String>) {
+        let imp = self.imp();
+
+        let default_subtitle = imp.default_subtitle.get_or_init(|| self.subtitle().unwrap_or_default());
+
+        let Some(rule) = imp.feature.get().and_then(|feature| rules.get(feature)) else {
+            // Feature no longer managed (e.g. after policy reload)
+            if imp.managed_icon.is_visible() {
+                imp.managed_icon.set_visible(false);
+
+                self.set_subtitle(default_subtitle);
+                self.set_sensitive(imp.selected_index.get().is_some());
+            }
+
+            return
+        };
+
+        imp.managed_icon.set_visible(true);
+        self.set_sensitive(imp.selected_index.get().is_some());
+
+        // Restricted values: list values that can still be applied
+        if let Some(allowed) = rule.strip_prefix("allowed:") {
+            let names = self.model()
+                .and_downcast::<adw::EnumListModel>()
+                .map(|model| {
+                    model.iter::<adw::EnumListItem>().flatten()
+                        .filter(|item| allowed.split(',').any(|value| value == item.value().to_string()))
+                        .map(|item| item.name().to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                })
+                .unwrap_or_default();
+
+            self.set_subtitle(&format!("Limited by your administrator to {names}"));
+        } else {
+            self.set_subtitle("Managed by your administrator");
+            self.set_sensitive(false);
         }
     }
 }
+
+//------------------------------------------------------------------------------
+// TESTS
+//------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use gtk::gio;
+
+    use super::*;
+    use crate::lg_gram::gram::Backend as _;
+
+    const OPTIMIZED: gram::Value = gram::Value::FanMode(gram::FanMode::Optimized);
+    const SILENT: gram::Value = gram::Value::FanMode(gram::FanMode::Silent);
+
+    const WRITE_ERROR: &str = "ERROR: Simulated write failure";
+
+    static RESOURCES: Once = Once::new();
+
+    //---------------------------------------
+    // Fixture struct
+    //---------------------------------------
+    // Fan mode widget in a window providing the win actions it uses, activations are recorded
+    struct Fixture {
+        backend: Rc<gram::MemoryBackend>,
+        widget: GramWidget,
+        activated: Rc<RefCell<Vec<(String, Option<String>)>>>,
+        _window: gtk::Window
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            adw::init().expect("Failed to initialize libadwaita");
+
+            RESOURCES.call_once(|| {
+                gio::resources_register_include!("resources.gresource")
+                    .expect("Failed to register resources");
+            });
+
+            let activated = Rc::new(RefCell::new(Vec::new()));
+
+            let actions = gio::SimpleActionGroup::new();
+
+            for (name, parameter) in [
+                ("show-error-toast", Some(glib::VariantTy::STRING)),
+                ("show-elevation-error", Some(glib::VariantTy::STRING)),
+                ("reload-policy", None)
+            ] {
+                let action = gio::SimpleAction::new(name, parameter);
+
+                let activated = Rc::clone(&activated);
+
+                action.connect_activate(move |action, parameter| {
+                    activated.borrow_mut().push((action.name().to_string(), parameter.and_then(|parameter| parameter.get::<String>())));
+                });
+
+                actions.add_action(&action);
+            }
+
+            let window = gtk::Window::new();
+
+            window.insert_action_group("win", Some(&actions));
+
+            let widget = glib::Object::new::<GramWidget>();
+
+            window.set_child(Some(&widget));
+
+            let backend = Rc::new(gram::MemoryBackend::default());
+            let snapshot = gram::Snapshot::take(backend.as_ref());
+
+            widget.init(gram::Feature::FanMode, &(Rc::clone(&backend) as Rc<dyn gram::Backend>), &snapshot);
+
+            Self { backend, widget, activated, _window: window }
+        }
+
+        // Select value as the user would, then wait for the write to finish
+        fn select(&self, value: gram::Value) {
+            let index = self.widget.index_of(value)
+                .expect("Failed to get value index");
+
+            self.widget.set_selected(index);
+
+            let context = glib::MainContext::default();
+
+            while context.pending() {
+                context.iteration(false);
+            }
+        }
+
+        fn selected_value(&self) -> Option<gram::Value> {
+            self.widget.selected_item()
+                .and_downcast::<adw::EnumListItem>()
+                .and_then(|item| gram::Value::from_raw(gram::Feature::FanMode, item.value()))
+        }
+
+        fn activated(&self) -> Vec<(String, Option<String>)> {
+            self.activated.borrow().clone()
+        }
+    }
+
+    #[gtk::test]
+    fn successful_write_keeps_selection() {
+        let fixture = Fixture::new();
+
+        fixture.select(SILENT);
+
+        assert_eq!(fixture.selected_value(), Some(SILENT));
+        assert_eq!(fixture.backend.feature(gram::Feature::FanMode).ok(), Some(SILENT));
+        assert!(fixture.activated().is_empty());
+    }
+
+    #[gtk::test]
+    fn failed_write_reverts_selection_and_shows_error() {
+        let fixture = Fixture::new();
+
+        fixture.backend.fail_writes(gram::Error::WriterFailed(gram::WriterCategory::Busy, String::from(WRITE_ERROR)));
+        fixture.select(SILENT);
+
+        assert_eq!(fixture.selected_value(), Some(OPTIMIZED));
+        assert_eq!(fixture.backend.feature(gram::Feature::FanMode).ok(), Some(OPTIMIZED));
+        assert_eq!(fixture.activated(), [(String::from("show-error-toast"), Some(String::from(WRITE_ERROR)))]);
+        assert!(fixture.widget.is_sensitive());
+    }
+
+    #[gtk::test]
+    fn cancelled_write_reverts_selection_silently() {
+        let fixture = Fixture::new();
+
+        fixture.backend.fail_writes(gram::Error::ElevationCancelled);
+        fixture.select(SILENT);
+
+        assert_eq!(fixture.selected_value(), Some(OPTIMIZED));
+        assert!(fixture.activated().is_empty());
+    }
+
+    #[gtk::test]
+    fn unavailable_elevation_shows_elevation_error() {
+        let fixture = Fixture::new();
+
+        fixture.backend.fail_writes(gram::Error::ElevationUnavailable(String::from(WRITE_ERROR)));
+        fixture.select(SILENT);
+
+        assert_eq!(fixture.selected_value(), Some(OPTIMIZED));
+        assert_eq!(fixture.activated(), [(String::from("show-elevation-error"), Some(String::from(WRITE_ERROR)))]);
+    }
+
+    #[gtk::test]
+    fn policy_error_reloads_policy() {
+        let fixture = Fixture::new();
+
+        fixture.backend.fail_writes(gram::Error::WriterFailed(gram::WriterCategory::Policy, String::from(WRITE_ERROR)));
+        fixture.select(SILENT);
+
+        assert_eq!(fixture.selected_value(), Some(OPTIMIZED));
+        assert_eq!(fixture.activated(), [
+            (String::from("reload-policy"), None),
+            (String::from("show-error-toast"), Some(String::from(WRITE_ERROR)))
+        ]);
+    }
+}
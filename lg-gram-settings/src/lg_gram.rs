@@ -2,29 +2,890 @@
 // MODULE: Gram
 //------------------------------------------------------------------------------
 pub mod gram {
+    use std::env;
     use std::fs;
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, HashMap};
+    use std::error;
+    use std::fmt;
+    use std::future::Future;
+    use std::io::{self, ErrorKind};
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::{Path, PathBuf};
+    use std::pin::Pin;
+    use std::process::{ExitStatus, Output, Stdio};
+    use std::rc::Rc;
+    use std::sync::Mutex;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-    use async_process::Command as AsyncCommand;
+    use gtk::glib;
+    use gtk::glib::prelude::StaticType;
+    use async_process::{Child, ChildStdin, ChildStdout, Command as AsyncCommand};
+    use futures_lite::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use serde::{Deserialize, Serialize};
 
     //---------------------------------------
     // Constants
     //---------------------------------------
     const WRITER: &str = "/usr/share/lg-gram-settings/lg-gram-writer";
     const SETTINGS_PATH: &str = "/sys/devices/platform/lg-laptop";
+    const OVERRIDES_PATH: &str = "/run/lg-gram-settings/overrides";
+
+    // Writer exit codes by error category (no agent: authentication required, but no polkit agent running)
+    const WRITER_NO_AGENT: i32 = 2;
+    const WRITER_NOT_AUTHORIZED: i32 = 3;
+    const WRITER_DISMISSED: i32 = 4;
+    const WRITER_POLICY: i32 = 5;
+    const WRITER_BUSY: i32 = 6;
+
+    // Pkexec exit code if authentication failed or was dismissed
+    const PKEXEC_NOT_AUTHORIZED: i32 = 126;
+
+    // Includes time spent in the polkit authentication dialog
+    const WRITER_TIMEOUT: Duration = Duration::from_secs(120);
+
+    // Sysfs attributes do not emit change notifications, so values are polled (each read queries the EC)
+    const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+    // Environment variable to force an elevation method (pkexec, run0 or sudo)
+    const ELEVATION_ENV: &str = "LG_GRAM_ELEVATION";
+
+    const ASKPASS_HELPERS: [&str; 4] = [
+        "/usr/lib/ssh/ssh-askpass",
+        "/usr/bin/ssh-askpass",
+        "/usr/libexec/openssh/gnome-ssh-askpass",
+        "/usr/bin/ksshaskpass",
+    ];
+
+    //---------------------------------------
+    // Elevation enum
+    //---------------------------------------
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Elevation {
+        Pkexec,
+        Run0,
+        Sudo
+    }
+
+    impl Elevation {
+        const ALL: [Self; 3] = [Self::Pkexec, Self::Run0, Self::Sudo];
+
+        pub fn name(self) -> &'static str {
+            match self {
+                Self::Pkexec => "pkexec",
+                Self::Run0 => "run0",
+                Self::Sudo => "sudo"
+            }
+        }
+
+        fn askpass() -> Option<String> {
+            env::var("SUDO_ASKPASS").ok()
+                .or_else(|| {
+                    ASKPASS_HELPERS.iter()
+                        .find(|helper| Path::new(helper).exists())
+                        .map(|helper| (*helper).to_owned())
+                })
+        }
+
+        fn is_available(self) -> bool {
+            match self {
+                Self::Pkexec | Self::Run0 => find_program(self.name()).is_some(),
+                Self::Sudo => find_program("sudo").is_some() && Self::askpass().is_some()
+            }
+        }
+
+        fn command(self) -> AsyncCommand {
+            let mut command = AsyncCommand::new(self.name());
+
+            if self == Self::Sudo {
+                command.arg("--askpass");
+
+                if let Some(askpass) = Self::askpass() {
+                    command.env("SUDO_ASKPASS", askpass);
+                }
+
+                command.arg("--");
+            }
+
+            command.arg(WRITER);
+
+            command
+        }
+
+        // Method cannot authenticate (e.g. no polkit agent), another method should be tried
+        fn is_unusable(self, output: &Output) -> bool {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            match self {
+                Self::Pkexec => {
+                    output.status.code() == Some(WRITER_NO_AGENT)
+                        || (output.status.code() == Some(127) && stderr.contains("authentication agent"))
+                },
+                Self::Run0 => stderr.contains("Interactive authentication required"),
+                Self::Sudo => false
+            }
+        }
+    }
+
+    // Elevation method that worked last
+    static ELEVATION: Mutex<Option<Elevation>> = Mutex::new(None);
+
+    // Elevation method selected with --elevation command line option
+    static SELECTED_ELEVATION: Mutex<Option<String>> = Mutex::new(None);
+
+    //---------------------------------------
+    // Select elevation function
+    //---------------------------------------
+    pub fn select_elevation(name: &str) {
+        *SELECTED_ELEVATION.lock().unwrap() = Some(name.to_owned());
+    }
+
+    //---------------------------------------
+    // Session struct
+    //---------------------------------------
+    // Elevated writer serving requests over stdin/stdout (see lg-gram-writer session)
+    struct Session {
+        child: Child,
+        stdin: ChildStdin,
+        stdout: BufReader<ChildStdout>
+    }
+
+    impl Session {
+        fn start(elevation: Elevation) -> Result<Self, Error> {
+            let mut child = elevation.command()
+                .arg("session")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|error| Error::io(&error))?;
+
+            let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+                return Err(Error::writer("ERROR: Failed to connect to writer session"))
+            };
+
+            Ok(Self { child, stdin, stdout: BufReader::new(stdout) })
+        }
+
+        // Returns None if session has ended (idle timeout or elevation failed)
+        async fn request(&mut self, args: &[&str]) -> Result<Option<Output>, Error> {
+            let exchange = async {
+                let request = format!("{}\n", args.join(" "));
+
+                if self.stdin.write_all(request.as_bytes()).await.is_err() || self.stdin.flush().await.is_err() {
+                    return Ok(None)
+                }
+
+                let mut header = String::new();
+
+                if self.stdout.read_line(&mut header).await.unwrap_or_default() == 0 {
+                    return Ok(None)
+                }
+
+                // Header is "OK <length>" or "ERROR <exit code> <length>"
+                let (status, length) = header.trim().rsplit_once(' ')
+                    .and_then(|(status, length)| Some((status, length.parse::<usize>().ok()?)))
+                    .ok_or_else(|| Error::writer("ERROR: Invalid response from writer session"))?;
+
+                let code = match status.split_once(' ') {
+                    None if status == "OK" => 0,
+                    Some(("ERROR", code)) => code.parse::<i32>()
+                        .map_err(|_| Error::writer("ERROR: Invalid response from writer session"))?,
+                    _ => return Err(Error::writer("ERROR: Invalid response from writer session"))
+                };
+
+                let mut response = vec![0_u8; length];
+
+                self.stdout.read_exact(&mut response).await
+                    .map_err(|error| Error::io(&error))?;
+
+                // Convert response to process output, so that callers handle both alike
+                let output = if code == 0 {
+                    Output { status: ExitStatus::from_raw(0), stdout: response, stderr: vec![] }
+                } else {
+                    Output { status: ExitStatus::from_raw(code << 8), stdout: vec![], stderr: response }
+                };
+
+                Ok::<Option<Output>, Error>(Some(output))
+            };
+
+            glib::future_with_timeout(WRITER_TIMEOUT, exchange).await
+                .map_err(|_| timeout_error())?
+        }
+
+        async fn finish(self) -> Result<Output, Error> {
+            // Closing stdin ends the session
+            let Self { child, stdin, stdout } = self;
+
+            drop(stdin);
+            drop(stdout);
+
+            glib::future_with_timeout(WRITER_TIMEOUT, child.output()).await
+                .map_err(|_| timeout_error())?
+                .map_err(|error| Error::io(&error))
+        }
+    }
+
+    static SESSION: async_lock::Mutex<Option<Session>> = async_lock::Mutex::new(None);
+
+    //---------------------------------------
+    // Apply enum
+    //---------------------------------------
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Apply {
+        Persistent,
+        UntilReboot,
+        ForMinutes(u32)
+    }
+
+    //---------------------------------------
+    // Feature enum
+    //---------------------------------------
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Feature {
+        BatteryCareLimit,
+        FnLock,
+        UsbCharge,
+        FanMode
+    }
+
+    impl Feature {
+        pub const ALL: [Self; 4] = [Self::BatteryCareLimit, Self::FnLock, Self::UsbCharge, Self::FanMode];
+
+        pub fn id(self) -> &'static str {
+            match self {
+                Self::BatteryCareLimit => "battery_care_limit",
+                Self::FnLock => "fn_lock",
+                Self::UsbCharge => "usb_charge",
+                Self::FanMode => "fan_mode"
+            }
+        }
+
+        pub fn from_id(id: &str) -> Option<Self> {
+            Self::ALL.into_iter()
+                .find(|feature| feature.id() == id)
+        }
+
+        // Enum type listing feature values (for EnumListModel)
+        pub fn enum_type(self) -> glib::Type {
+            match self {
+                Self::BatteryCareLimit => BatteryCareLimit::static_type(),
+                Self::FnLock | Self::UsbCharge => OnOff::static_type(),
+                Self::FanMode => FanMode::static_type()
+            }
+        }
+    }
+
+    //---------------------------------------
+    // BatteryCareLimit enum
+    //---------------------------------------
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, glib::Enum, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    #[repr(u32)]
+    #[enum_type(name = "BatteryCareLimit")]
+    pub enum BatteryCareLimit {
+        #[default]
+        #[enum_value(name = "No Limit")]
+        NoLimit = 100,
+        #[enum_value(name = "Limit to 80%")]
+        Limit80 = 80
+    }
+
+    impl BatteryCareLimit {
+        fn from_raw(raw: i32) -> Option<Self> {
+            match raw {
+                100 => Some(Self::NoLimit),
+                80 => Some(Self::Limit80),
+                _ => None
+            }
+        }
+    }
+
+    //---------------------------------------
+    // OnOff enum
+    //---------------------------------------
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, glib::Enum, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    #[repr(u32)]
+    #[enum_type(name = "OnOff")]
+    pub enum OnOff {
+        #[default]
+        Disabled = 0,
+        Enabled = 1
+    }
+
+    impl OnOff {
+        fn from_raw(raw: i32) -> Option<Self> {
+            match raw {
+                0 => Some(Self::Disabled),
+                1 => Some(Self::Enabled),
+                _ => None
+            }
+        }
+    }
+
+    //---------------------------------------
+    // FanMode enum
+    //---------------------------------------
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, glib::Enum, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    #[repr(u32)]
+    #[enum_type(name = "FanMode")]
+    pub enum FanMode {
+        #[default]
+        Optimized = 0,
+        Silent = 1,
+        Performance = 2
+    }
+
+    impl FanMode {
+        fn from_raw(raw: i32) -> Option<Self> {
+            match raw {
+                0 => Some(Self::Optimized),
+                1 => Some(Self::Silent),
+                2 => Some(Self::Performance),
+                _ => None
+            }
+        }
+    }
+
+    //---------------------------------------
+    // Value enum
+    //---------------------------------------
+    // Feature value, only valid combinations can be constructed
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Value {
+        BatteryCareLimit(BatteryCareLimit),
+        FnLock(OnOff),
+        UsbCharge(OnOff),
+        FanMode(FanMode)
+    }
+
+    impl Value {
+        pub fn feature(self) -> Feature {
+            match self {
+                Self::BatteryCareLimit(_) => Feature::BatteryCareLimit,
+                Self::FnLock(_) => Feature::FnLock,
+                Self::UsbCharge(_) => Feature::UsbCharge,
+                Self::FanMode(_) => Feature::FanMode
+            }
+        }
+
+        // Raw value, equal to enum value in EnumListModel
+        pub fn raw(self) -> i32 {
+            match self {
+                Self::BatteryCareLimit(limit) => limit as i32,
+                Self::FnLock(state) | Self::UsbCharge(state) => state as i32,
+                Self::FanMode(mode) => mode as i32
+            }
+        }
+
+        pub fn from_raw(feature: Feature, raw: i32) -> Option<Self> {
+            match feature {
+                Feature::BatteryCareLimit => BatteryCareLimit::from_raw(raw).map(Self::BatteryCareLimit),
+                Feature::FnLock => OnOff::from_raw(raw).map(Self::FnLock),
+                Feature::UsbCharge => OnOff::from_raw(raw).map(Self::UsbCharge),
+                Feature::FanMode => FanMode::from_raw(raw).map(Self::FanMode)
+            }
+        }
+
+        pub fn parse(feature: Feature, value: &str) -> Result<Self, Error> {
+            value.trim().parse::<i32>().ok()
+                .and_then(|raw| Self::from_raw(feature, raw))
+                .ok_or_else(|| Error::UnexpectedValue(feature, value.trim().to_owned()))
+        }
+    }
+
+    impl fmt::Display for Value {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.raw())
+        }
+    }
+
+    //---------------------------------------
+    // Reading enum
+    //---------------------------------------
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Reading {
+        Value(Value),
+        Missing,
+        Failed(String)
+    }
+
+    //---------------------------------------
+    // Snapshot struct
+    //---------------------------------------
+    // All feature values, read once at a single point in time
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Snapshot {
+        pub time: u64,
+        pub readings: BTreeMap<Feature, Reading>
+    }
+
+    impl Snapshot {
+        pub fn take(backend: &dyn Backend) -> Self {
+            let readings = Feature::ALL.into_iter()
+                .map(|feature| {
+                    let reading = match backend.feature(feature) {
+                        Ok(value) => Reading::Value(value),
+                        Err(Error::AttributeMissing(_)) => Reading::Missing,
+                        Err(error) => Reading::Failed(error.to_string())
+                    };
+
+                    (feature, reading)
+                })
+                .collect();
+
+            Self { time: now(), readings }
+        }
+
+        pub fn reading(&self, feature: Feature) -> &Reading {
+            self.readings.get(&feature)
+                .unwrap_or(&Reading::Missing)
+        }
+
+        pub fn value(&self, feature: Feature) -> Option<Value> {
+            match self.reading(feature) {
+                Reading::Value(value) => Some(*value),
+                _ => None
+            }
+        }
+
+        // Values that differ from previous snapshot
+        pub fn changes(&self, previous: &Self) -> Vec<Value> {
+            Feature::ALL.into_iter()
+                .filter_map(|feature| self.value(feature).filter(|value| previous.value(feature) != Some(*value)))
+                .collect()
+        }
+    }
+
+    //---------------------------------------
+    // Watcher struct
+    //---------------------------------------
+    // Reports values changed outside the app (e.g. Fn-lock hotkey or other tools), polls only while started
+    pub struct Watcher {
+        check: Rc<dyn Fn()>,
+        source: RefCell<Option<glib::SourceId>>
+    }
+
+    impl Watcher {
+        pub fn new(backend: Rc<dyn Backend>, callback: impl Fn(Value) + 'static) -> Self {
+            let snapshot = RefCell::new(Snapshot::take(backend.as_ref()));
+
+            let check = Rc::new(move || {
+                let current = Snapshot::take(backend.as_ref());
+                let previous = snapshot.replace(current.clone());
+
+                for value in current.changes(&previous) {
+                    callback(value);
+                }
+            });
+
+            Self { check, source: RefCell::new(None) }
+        }
+
+        // Check now, then poll until stopped
+        pub fn start(&self) {
+            if self.source.borrow().is_some() {
+                return
+            }
+
+            (self.check)();
+
+            let check = Rc::clone(&self.check);
+
+            let source = glib::timeout_add_local(POLL_INTERVAL, move || {
+                check();
+
+                glib::ControlFlow::Continue
+            });
+
+            self.source.replace(Some(source));
+        }
+
+        pub fn stop(&self) {
+            if let Some(source) = self.source.take() {
+                source.remove();
+            }
+        }
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    //---------------------------------------
+    // WriterCategory enum
+    //---------------------------------------
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum WriterCategory {
+        Policy,
+        Busy,
+        Timeout,
+        Security,
+        Other
+    }
+
+    //---------------------------------------
+    // Error enum
+    //---------------------------------------
+    #[derive(Debug, Clone)]
+    pub enum Error {
+        AttributeMissing(Feature),
+        PermissionDenied(String),
+        ElevationCancelled,
+        ElevationUnavailable(String),
+        WriterFailed(WriterCategory, String),
+        UnexpectedValue(Feature, String)
+    }
+
+    impl Error {
+        fn writer(message: impl Into<String>) -> Self {
+            Self::WriterFailed(WriterCategory::Other, message.into())
+        }
+
+        fn io(error: &io::Error) -> Self {
+            Self::writer(format!("ERROR: Failed to run writer: {error}"))
+        }
+
+        // Classify failed writer output by exit code (writer errors or elevation errors)
+        fn from_output(output: &Output) -> Self {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+
+            match output.status.code() {
+                Some(WRITER_NO_AGENT) => Self::ElevationUnavailable(message),
+                Some(WRITER_NOT_AUTHORIZED) => Self::PermissionDenied(message),
+                Some(WRITER_DISMISSED) => Self::ElevationCancelled,
+                Some(WRITER_POLICY) => Self::WriterFailed(WriterCategory::Policy, message),
+                Some(WRITER_BUSY) => Self::WriterFailed(WriterCategory::Busy, message),
+                // Pkexec uses one exit code for both, "Request dismissed" is not translated
+                Some(PKEXEC_NOT_AUTHORIZED) if message.ends_with("Request dismissed") => Self::ElevationCancelled,
+                Some(PKEXEC_NOT_AUTHORIZED) => Self::PermissionDenied(message),
+                // Sudo askpass helper was closed (sudo has no distinct exit code)
+                Some(1) if message.contains("no password was provided") => Self::ElevationCancelled,
+                _ => Self::WriterFailed(WriterCategory::Other, message)
+            }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::AttributeMissing(feature) => write!(f, "ERROR: {} attribute not found (is the lg-laptop driver loaded?)", feature.id()),
+                Self::ElevationCancelled => write!(f, "ERROR: Authentication was cancelled"),
+                Self::UnexpectedValue(feature, value) => write!(f, "ERROR: Unexpected {} value '{value}'", feature.id()),
+                Self::PermissionDenied(message) | Self::ElevationUnavailable(message) | Self::WriterFailed(_, message) => {
+                    write!(f, "{message}")
+                }
+            }
+        }
+    }
+
+    impl error::Error for Error {}
+
+    //---------------------------------------
+    // Run writer helper function
+    //---------------------------------------
+    async fn run_writer(command: &mut AsyncCommand) -> Result<Output, Error> {
+        let output = command.kill_on_drop(true).output();
+
+        glib::future_with_timeout(WRITER_TIMEOUT, output).await
+            .map_err(|_| timeout_error())?
+            .map_err(|error| Error::io(&error))
+    }
+
+    //---------------------------------------
+    // Timeout error helper function
+    //---------------------------------------
+    fn timeout_error() -> Error {
+        Error::WriterFailed(WriterCategory::Timeout, format!("ERROR: Writer did not respond within {} seconds", WRITER_TIMEOUT.as_secs()))
+    }
+
+    //---------------------------------------
+    // Security error helper function
+    //---------------------------------------
+    fn security_error(message: String) -> Error {
+        Error::WriterFailed(WriterCategory::Security, message)
+    }
+
+    //---------------------------------------
+    // Find program helper function
+    //---------------------------------------
+    fn find_program(name: &str) -> Option<PathBuf> {
+        env::var("PATH").unwrap_or_default()
+            .split(':')
+            .map(|dir| Path::new(dir).join(name))
+            .find(|path| path.exists())
+    }
+
+    //---------------------------------------
+    // Check path helper function
+    //---------------------------------------
+    fn check_path(path: &Path) -> Result<(), Error> {
+        // Writer runs as root, so neither it nor any parent directory may be changeable by users
+        for ancestor in path.ancestors() {
+            let metadata = fs::symlink_metadata(ancestor)
+                .map_err(|error| security_error(format!("ERROR: Refusing to run writer, failed to check {} ({error})", ancestor.display())))?;
+
+            if metadata.uid() != 0 {
+                return Err(security_error(format!("ERROR: Refusing to run writer for security reasons: {} is not owned by root", ancestor.display())))
+            }
+
+            // Permissions of symbolic links are not used
+            if !metadata.is_symlink() && metadata.mode() & 0o022 != 0 {
+                return Err(security_error(format!("ERROR: Refusing to run writer for security reasons: {} is writable by group or others", ancestor.display())))
+            }
+        }
+
+        Ok(())
+    }
+
+    //---------------------------------------
+    // Verify writer helper function
+    //---------------------------------------
+    async fn verify_writer() -> Result<(), Error> {
+        // Check both the installed path and the path it resolves to
+        let resolved = fs::canonicalize(WRITER)
+            .map_err(|error| security_error(format!("ERROR: Refusing to run writer, failed to resolve {WRITER} ({error})")))?;
+
+        check_path(Path::new(WRITER))?;
+        check_path(&resolved)?;
+
+        if !fs::metadata(&resolved).is_ok_and(|metadata| metadata.is_file()) {
+            return Err(security_error(format!("ERROR: Refusing to run writer for security reasons: {WRITER} is not a regular file")))
+        }
+
+        // Version handshake, run without elevation
+        let output = run_writer(AsyncCommand::new(WRITER).arg("--version")).await?;
+
+        let version = String::from_utf8_lossy(&output.stdout);
+        let expected = format!("lg-gram-writer {}", env!("CARGO_PKG_VERSION"));
+
+        if version.trim() != expected {
+            return Err(security_error(format!("ERROR: Refusing to run writer: version mismatch (found '{}', expected '{expected}')", version.trim())))
+        }
+
+        Ok(())
+    }
+
+    //---------------------------------------
+    // Elevation candidates helper function
+    //---------------------------------------
+    fn elevation_candidates() -> Result<Vec<Elevation>, Error> {
+        // User override (command line option takes precedence over environment variable)
+        let selected = SELECTED_ELEVATION.lock().unwrap().clone()
+            .map(|name| (name, "--elevation"))
+            .or_else(|| env::var(ELEVATION_ENV).ok().map(|name| (name, ELEVATION_ENV)));
+
+        if let Some((name, source)) = selected {
+            let elevation = Elevation::ALL.into_iter()
+                .find(|elevation| elevation.name() == name)
+                .ok_or_else(|| Error::ElevationUnavailable(format!("ERROR: Unknown elevation method {name} selected with {source} (use pkexec, run0 or sudo)")))?;
+
+            if !elevation.is_available() {
+                return Err(Error::ElevationUnavailable(format!("ERROR: Elevation method {name} selected with {source} is not available")))
+            }
+
+            return Ok(vec![elevation])
+        }
+
+        // Method that worked last is tried first
+        let working = *ELEVATION.lock().unwrap();
+
+        let candidates: Vec<Elevation> = working.into_iter()
+            .chain(Elevation::ALL.into_iter().filter(|elevation| Some(*elevation) != working && elevation.is_available()))
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(Error::ElevationUnavailable(String::from("ERROR: No elevation method available (install polkit with an authentication agent, systemd 256 or newer for run0, or sudo with an askpass helper)")))
+        }
+
+        Ok(candidates)
+    }
+
+    //---------------------------------------
+    // Run elevated helper function
+    //---------------------------------------
+    async fn run_elevated(args: &[&str]) -> Result<Output, Error> {
+        verify_writer().await?;
+
+        for elevation in elevation_candidates()? {
+            let output = run_writer(elevation.command().args(args)).await?;
+
+            if elevation.is_unusable(&output) {
+                continue
+            }
+
+            *ELEVATION.lock().unwrap() = Some(elevation);
+
+            return Ok(output)
+        }
+
+        Err(Error::ElevationUnavailable(String::from("ERROR: No elevation method could authenticate (is a polkit authentication agent running?)")))
+    }
+
+    //---------------------------------------
+    // Run session helper function
+    //---------------------------------------
+    async fn run_session(args: &[&str]) -> Result<Output, Error> {
+        let mut session = SESSION.lock().await;
+
+        // Reuse running session
+        if let Some(running) = session.as_mut() {
+            match running.request(args).await {
+                Ok(Some(output)) => return Ok(output),
+                Ok(None) => {},
+                Err(error) => {
+                    *session = None;
+
+                    return Err(error)
+                }
+            }
+        }
+
+        // Session ended after idle timeout, start new session
+        if let Some(ended) = session.take() {
+            let _ = ended.finish().await;
+        }
+
+        verify_writer().await?;
+
+        for elevation in elevation_candidates()? {
+            let mut started = Session::start(elevation)?;
+
+            if let Some(output) = started.request(args).await? {
+                // Session started, but writer could not authenticate request
+                if elevation.is_unusable(&output) {
+                    let _ = started.finish().await;
+
+                    continue
+                }
+
+                *ELEVATION.lock().unwrap() = Some(elevation);
+                *session = Some(started);
+
+                return Ok(output)
+            }
+
+            // Session ended before first response, check if elevation failed
+            let output = started.finish().await?;
+
+            if elevation.is_unusable(&output) {
+                continue
+            }
+
+            return Ok(output)
+        }
+
+        Err(Error::ElevationUnavailable(String::from("ERROR: No elevation method could authenticate (is a polkit authentication agent running?)")))
+    }
+
+    //---------------------------------------
+    // Run reader helper function
+    //---------------------------------------
+    async fn run_reader(args: &[&str]) -> Result<Output, Error> {
+        // Use running session, but do not elevate only to read
+        if SESSION.lock().await.is_some() {
+            return run_session(args).await
+        }
+
+        run_writer(AsyncCommand::new(WRITER).args(args)).await
+    }
+
+    //---------------------------------------
+    // End session function
+    //---------------------------------------
+    fn end_session() {
+        // Dropping session closes stdin, so that writer exits
+        if let Some(mut session) = SESSION.try_lock() {
+            session.take();
+        }
+    }
 
     //---------------------------------------
     // System information function
     //---------------------------------------
-    pub async fn system_information_async() -> Result<String, String> {
-        let output = AsyncCommand::new("pkexec")
-            .arg(WRITER)
-            .arg("--system-info")
-            .output()
-            .await
-            .map_err(|error| error.to_string())?;
+    async fn system_information_async(reveal: bool) -> Result<String, Error> {
+        let args: &[&str] = if reveal { &["system-info", "--reveal"] } else { &["system-info"] };
+
+        let output = run_session(args).await?;
+
+        if !output.status.success() {
+            return Err(Error::from_output(&output))
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into())
+    }
+
+    //---------------------------------------
+    // Diagnostics function
+    //---------------------------------------
+    async fn diagnostics_async() -> Result<Vec<u8>, Error> {
+        // Archive is written to stdout, which session does not support
+        let output = run_elevated(&["diagnostics"]).await?;
+
+        if !output.status.success() {
+            return Err(Error::from_output(&output))
+        }
+
+        Ok(output.stdout)
+    }
+
+    //---------------------------------------
+    // Self check function
+    //---------------------------------------
+    async fn self_check_async() -> Result<String, Error> {
+        // Run without elevation, so that check works if elevation is broken
+        let output = match run_writer(AsyncCommand::new(WRITER).arg("self-check")).await {
+            Ok(output) => output,
+            Err(error) => {
+                let error = error.to_string();
+
+                return Ok(format!("FAIL Writer: {WRITER} could not be run ({})\n  FIX Install lg-gram-writer to {WRITER}", error.trim_start_matches("ERROR: ")))
+            }
+        };
+
+        // Report is printed to stdout, even if checks failed
+        if output.stdout.is_empty() {
+            return Err(Error::from_output(&output))
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into())
+    }
+
+    //---------------------------------------
+    // Unit problems function
+    //---------------------------------------
+    async fn unit_problems_async() -> Result<Option<String>, Error> {
+        let output = run_writer(AsyncCommand::new(WRITER).args(["repair", "--check"])).await?;
+
+        if output.status.success() {
+            return Ok(None)
+        }
+
+        // Report is printed to stdout if problems were found
+        if output.stdout.is_empty() {
+            return Err(Error::from_output(&output))
+        }
+
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into()))
+    }
+
+    //---------------------------------------
+    // Repair units function
+    //---------------------------------------
+    async fn repair_units_async() -> Result<String, Error> {
+        let output = run_session(&["repair"]).await?;
 
         if !output.status.success() {
-            return Err(String::from_utf8_lossy(&output.stderr).into())
+            return Err(Error::from_output(&output))
         }
 
         Ok(String::from_utf8_lossy(&output.stdout).into())
@@ -33,33 +894,349 @@ pub mod gram {
     //---------------------------------------
     // Feature function
     //---------------------------------------
-    pub fn feature(id: &str) -> Result<String, String> {
-        let file = format!("{SETTINGS_PATH}/{id}");
+    fn feature(feature: Feature) -> Result<Value, Error> {
+        let file = format!("{SETTINGS_PATH}/{}", feature.id());
+
+        let value = fs::read_to_string(&file)
+            .map_err(|error| match error.kind() {
+                ErrorKind::NotFound => Error::AttributeMissing(feature),
+                ErrorKind::PermissionDenied => Error::PermissionDenied(format!("ERROR: Permission denied reading {file}")),
+                _ => Error::writer(format!("ERROR: Failed to read {file}: {error}"))
+            })?;
+
+        Value::parse(feature, &value)
+    }
+
+    //---------------------------------------
+    // Feature override function
+    //---------------------------------------
+    fn feature_override(feature: Feature) -> Option<(Value, u64)> {
+        let content = fs::read_to_string(format!("{OVERRIDES_PATH}/{}", feature.id())).ok()?;
 
-        fs::metadata(&file)
-            .map_err(|_| String::from("file not found"))?;
+        let field = |key: &str| {
+            content.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(str::trim)
+        };
 
-        fs::read_to_string(file)
-            .map_err(|error| error.to_string())
-            .map(|value| value.trim().to_owned())
+        Some((Value::parse(feature, field("value")?).ok()?, field("expiry")?.parse().ok()?))
+    }
+
+    //---------------------------------------
+    // Override remaining function
+    //---------------------------------------
+    pub fn override_remaining(expiry: u64) -> u64 {
+        expiry.saturating_sub(now())
+    }
+
+    //---------------------------------------
+    // Now helper function
+    //---------------------------------------
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()
+    }
+
+    //---------------------------------------
+    // Persisted features function
+    //---------------------------------------
+    async fn persisted_features_async() -> Result<HashMap<Feature, Value>, Error> {
+        let output = run_reader(&["get", "--persisted"]).await?;
+
+        if !output.status.success() {
+            return Err(Error::from_output(&output))
+        }
+
+        let values = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (id, value) = line.split_once('=')?;
+                let feature = Feature::from_id(id)?;
+
+                Some((feature, Value::parse(feature, value).ok()?))
+            })
+            .collect();
+
+        Ok(values)
+    }
+
+    //---------------------------------------
+    // Policy function
+    //---------------------------------------
+    async fn policy_async() -> Result<HashMap<Feature, String>, Error> {
+        let output = run_reader(&["policy"]).await?;
+
+        if !output.status.success() {
+            return Err(Error::from_output(&output))
+        }
+
+        let rules = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (id, rule) = line.split_once('=')?;
+
+                Some((Feature::from_id(id)?, rule.to_owned()))
+            })
+            .collect();
+
+        Ok(rules)
     }
 
     //---------------------------------------
     // Set feature function
     //---------------------------------------
-    pub async fn set_feature_async(id: &str, value: &str) -> Result<String, String> {
-        let output = AsyncCommand::new("pkexec")
-            .arg(WRITER)
-            .arg("--feature")
-            .arg(format!("{id}={value}"))
-            .output()
-            .await
-            .map_err(|error| error.to_string())?;
+    async fn set_feature_async(value: Value, apply: Apply) -> Result<String, Error> {
+        let id = value.feature().id();
+        let value = value.to_string();
+
+        let duration = match apply {
+            Apply::ForMinutes(minutes) => format!("{minutes}m"),
+            _ => String::new()
+        };
+
+        let args: &[&str] = match apply {
+            Apply::Persistent => &["set", id, &value],
+            Apply::UntilReboot => &["set", "--runtime", id, &value],
+            Apply::ForMinutes(_) => &["set", "--for", &duration, id, &value]
+        };
+
+        let output = run_session(args).await?;
 
         if !output.status.success() {
-            return Err(String::from_utf8_lossy(&output.stderr).into())
+            return Err(Error::from_output(&output))
         }
 
         Ok(String::from_utf8_lossy(&output.stdout).into())
     }
+
+    //---------------------------------------
+    // Revert feature function
+    //---------------------------------------
+    async fn revert_feature_async(feature: Feature) -> Result<String, Error> {
+        let output = run_session(&["revert", feature.id()]).await?;
+
+        if !output.status.success() {
+            return Err(Error::from_output(&output))
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into())
+    }
+
+    //---------------------------------------
+    // Backend trait
+    //---------------------------------------
+    pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+    // Reads and writes features, injected into MainWindow and GramWidget
+    pub trait Backend {
+        fn feature(&self, feature: Feature) -> Result<Value, Error>;
+        fn feature_override(&self, feature: Feature) -> Option<(Value, u64)>;
+        fn set_feature(&self, value: Value, apply: Apply) -> BoxFuture<'_, Result<String, Error>>;
+        fn revert_feature(&self, feature: Feature) -> BoxFuture<'_, Result<String, Error>>;
+        fn persisted_features(&self) -> BoxFuture<'_, Result<HashMap<Feature, Value>, Error>>;
+        fn policy(&self) -> BoxFuture<'_, Result<HashMap<Feature, String>, Error>>;
+        fn system_information(&self, reveal: bool) -> BoxFuture<'_, Result<String, Error>>;
+        fn diagnostics(&self) -> BoxFuture<'_, Result<Vec<u8>, Error>>;
+        fn self_check(&self) -> BoxFuture<'_, Result<String, Error>>;
+        fn unit_problems(&self) -> BoxFuture<'_, Result<Option<String>, Error>>;
+        fn repair_units(&self) -> BoxFuture<'_, Result<String, Error>>;
+        fn close(&self) {}
+    }
+
+    //---------------------------------------
+    // WriterBackend struct
+    //---------------------------------------
+    // Reads sysfs directly, writes through elevated writer
+    pub struct WriterBackend;
+
+    impl Backend for WriterBackend {
+        fn feature(&self, feature: Feature) -> Result<Value, Error> {
+            self::feature(feature)
+        }
+
+        fn feature_override(&self, feature: Feature) -> Option<(Value, u64)> {
+            self::feature_override(feature)
+        }
+
+        fn set_feature(&self, value: Value, apply: Apply) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(set_feature_async(value, apply))
+        }
+
+        fn revert_feature(&self, feature: Feature) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(revert_feature_async(feature))
+        }
+
+        fn persisted_features(&self) -> BoxFuture<'_, Result<HashMap<Feature, Value>, Error>> {
+            Box::pin(persisted_features_async())
+        }
+
+        fn policy(&self) -> BoxFuture<'_, Result<HashMap<Feature, String>, Error>> {
+            Box::pin(policy_async())
+        }
+
+        fn system_information(&self, reveal: bool) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(system_information_async(reveal))
+        }
+
+        fn diagnostics(&self) -> BoxFuture<'_, Result<Vec<u8>, Error>> {
+            Box::pin(diagnostics_async())
+        }
+
+        fn self_check(&self) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(self_check_async())
+        }
+
+        fn unit_problems(&self) -> BoxFuture<'_, Result<Option<String>, Error>> {
+            Box::pin(unit_problems_async())
+        }
+
+        fn repair_units(&self) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(repair_units_async())
+        }
+
+        fn close(&self) {
+            end_session();
+        }
+    }
+
+    //---------------------------------------
+    // MemoryBackend struct
+    //---------------------------------------
+    // Keeps values in memory, for testing the GUI without hardware or root
+    #[cfg(test)]
+    pub struct MemoryBackend {
+        values: RefCell<HashMap<Feature, Value>>,
+        persisted: RefCell<HashMap<Feature, Value>>,
+        write_error: RefCell<Option<Error>>
+    }
+
+    #[cfg(test)]
+    impl Default for MemoryBackend {
+        fn default() -> Self {
+            let values: HashMap<Feature, Value> = [
+                Value::BatteryCareLimit(BatteryCareLimit::NoLimit),
+                Value::FnLock(OnOff::Disabled),
+                Value::UsbCharge(OnOff::Disabled),
+                Value::FanMode(FanMode::Optimized)
+            ].into_iter()
+                .map(|value| (value.feature(), value))
+                .collect();
+
+            Self {
+                persisted: RefCell::new(values.clone()),
+                values: RefCell::new(values),
+                write_error: RefCell::default()
+            }
+        }
+    }
+
+    #[cfg(test)]
+    impl MemoryBackend {
+        // Make all later writes fail with error
+        pub fn fail_writes(&self, error: Error) {
+            self.write_error.replace(Some(error));
+        }
+
+        fn write(&self, value: Value) -> Result<(), Error> {
+            if let Some(error) = self.write_error.borrow().clone() {
+                return Err(error)
+            }
+
+            self.values.borrow_mut().get_mut(&value.feature())
+                .map(|current| *current = value)
+                .ok_or(Error::AttributeMissing(value.feature()))
+        }
+    }
+
+    #[cfg(test)]
+    impl Backend for MemoryBackend {
+        fn feature(&self, feature: Feature) -> Result<Value, Error> {
+            self.values.borrow().get(&feature).copied()
+                .ok_or(Error::AttributeMissing(feature))
+        }
+
+        fn feature_override(&self, _: Feature) -> Option<(Value, u64)> {
+            None
+        }
+
+        fn set_feature(&self, value: Value, apply: Apply) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(async move {
+                self.write(value)?;
+
+                if apply == Apply::Persistent {
+                    self.persisted.borrow_mut().insert(value.feature(), value);
+                }
+
+                Ok(format!("Successfully changed {} setting", value.feature().id()))
+            })
+        }
+
+        fn revert_feature(&self, feature: Feature) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(async move {
+                let persisted = self.persisted.borrow().get(&feature).copied();
+
+                if let Some(value) = persisted {
+                    self.write(value)?;
+                }
+
+                Ok(format!("Successfully reverted {} setting", feature.id()))
+            })
+        }
+
+        fn persisted_features(&self) -> BoxFuture<'_, Result<HashMap<Feature, Value>, Error>> {
+            Box::pin(async move { Ok(self.persisted.borrow().clone()) })
+        }
+
+        fn policy(&self) -> BoxFuture<'_, Result<HashMap<Feature, String>, Error>> {
+            Box::pin(async move { Ok(HashMap::new()) })
+        }
+
+        fn system_information(&self, _: bool) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(async move { Ok(String::from("[Backend]\nType\nIn-memory")) })
+        }
+
+        fn diagnostics(&self) -> BoxFuture<'_, Result<Vec<u8>, Error>> {
+            Box::pin(async move { Err(Error::writer("ERROR: Diagnostics are not available with the in-memory backend")) })
+        }
+
+        fn self_check(&self) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(async move { Ok(String::from("PASS Backend: In-memory backend, nothing to check")) })
+        }
+
+        fn unit_problems(&self) -> BoxFuture<'_, Result<Option<String>, Error>> {
+            Box::pin(async move { Ok(None) })
+        }
+
+        fn repair_units(&self) -> BoxFuture<'_, Result<String, Error>> {
+            Box::pin(async move { Ok(String::from("No unit problems found")) })
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// TESTS
+//------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::gram::*;
+
+    #[test]
+    fn snapshot_round_trips_through_serialization() {
+        let snapshot = Snapshot {
+            time: 1_700_000_000,
+            readings: BTreeMap::from([
+                (Feature::BatteryCareLimit, Reading::Value(Value::BatteryCareLimit(BatteryCareLimit::Limit80))),
+                (Feature::FnLock, Reading::Value(Value::FnLock(OnOff::Enabled))),
+                (Feature::UsbCharge, Reading::Missing),
+                (Feature::FanMode, Reading::Failed(String::from("ERROR: Error reading fan_mode setting file"))),
+            ])
+        };
+
+        let serialized = toml::to_string(&snapshot).unwrap();
+
+        assert_eq!(toml::from_str::<Snapshot>(&serialized).unwrap(), snapshot);
+    }
 }
@@ -1,58 +1,19 @@
+use std::cell::{OnceCell, RefCell};
+use std::rc::Rc;
+
 use gtk::{gio, glib, pango};
 use adw::subclass::prelude::*;
 use adw::prelude::*;
 use gio::{AppInfo, AppLaunchContext};
 
 use crate::Application;
-use crate::gram_widget::GramWidget;
+use crate::gram_widget::{ApplyMode, GramWidget};
 use crate::lg_gram::gram;
 
 //------------------------------------------------------------------------------
 // CONSTANTS
 //------------------------------------------------------------------------------
-const BATTERY_LIMIT: &str = "battery_care_limit";
-const FN_LOCK: &str = "fn_lock";
-const USB_CHARGE: &str = "usb_charge";
-const FAN_MODE: &str = "fan_mode";
-
-//------------------------------------------------------------------------------
-// ENUM: BatteryCareLimit
-//------------------------------------------------------------------------------
-#[derive(Default, Debug, Clone, Copy, glib::Enum)]
-#[repr(u32)]
-#[enum_type(name = "BatteryCareLimit")]
-pub enum BatteryCareLimit {
-    #[default]
-    #[enum_value(name = "No Limit")]
-    NoLimit = 100,
-    #[enum_value(name = "Limit to 80%")]
-    Limit80 = 80
-}
-
-//------------------------------------------------------------------------------
-// ENUM: OnOff
-//------------------------------------------------------------------------------
-#[derive(Default, Debug, Clone, Copy, glib::Enum)]
-#[repr(u32)]
-#[enum_type(name = "OnOff")]
-pub enum OnOff {
-    #[default]
-    Disabled = 0,
-    Enabled = 1
-}
-
-//------------------------------------------------------------------------------
-// ENUM: FanMode
-//------------------------------------------------------------------------------
-#[derive(Default, Debug, Clone, Copy, glib::Enum)]
-#[repr(u32)]
-#[enum_type(name = "FanMode")]
-pub enum FanMode {
-    #[default]
-    Optimized = 0,
-    Silent = 1,
-    Performance = 2
-}
+const RESUME_DELAY: u32 = 3;
 
 //------------------------------------------------------------------------------
 // MODULE: MainWindow
@@ -70,6 +31,10 @@ mod imp {
     pub struct MainWindow {
         #[template_child]
         pub(super) toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub(super) apply_mode_dropdown: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub(super) units_banner: TemplateChild<adw::Banner>,
 
         #[template_child]
         pub(super) battery_limit_widget: TemplateChild<GramWidget>,
@@ -79,6 +44,11 @@ mod imp {
         pub(super) usb_charge_widget: TemplateChild<GramWidget>,
         #[template_child]
         pub(super) fan_mode_widget: TemplateChild<GramWidget>,
+
+        pub(super) unit_problems: RefCell<Option<String>>,
+        pub(super) sleep_subscription: RefCell<Option<gio::SignalSubscription>>,
+        pub(super) watcher: RefCell<Option<gram::Watcher>>,
+        pub(super) backend: OnceCell<Rc<dyn gram::Backend>>,
      }
 
     //---------------------------------------
@@ -109,16 +79,82 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
-            self.obj().init_kernel_features();
+            let obj = self.obj();
+
+            obj.setup_widgets();
+            obj.setup_signals();
         }
     }
 
     impl WidgetImpl for MainWindow {}
-    impl WindowImpl for MainWindow {}
+
+    impl WindowImpl for MainWindow {
+        //---------------------------------------
+        // Close request handler
+        //---------------------------------------
+        fn close_request(&self) -> glib::Propagation {
+            // Stop watching features and close backend (ends privileged writer session)
+            self.watcher.take();
+
+            if let Some(backend) = self.backend.get() {
+                backend.close();
+            }
+
+            self.parent_close_request()
+        }
+    }
+
     impl ApplicationWindowImpl for MainWindow {}
     impl AdwApplicationWindowImpl for MainWindow {}
 
     impl MainWindow {
+        //---------------------------------------
+        // Info page helper function
+        //---------------------------------------
+        fn info_page(info: &str) -> adw::PreferencesPage {
+            let page = adw::PreferencesPage::new();
+
+            let mut group: Option<adw::PreferencesGroup> = None;
+            let mut iter = info.lines();
+
+            while let Some(line) = iter.next() {
+                // Section header line
+                if let Some(title) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                    let section = adw::PreferencesGroup::builder()
+                        .title(title)
+                        .build();
+
+                    page.add(&section);
+
+                    group = Some(section);
+
+                    continue
+                }
+
+                let Some(value) = iter.next() else {
+                    break
+                };
+
+                let group = group.get_or_insert_with(|| {
+                    let section = adw::PreferencesGroup::new();
+
+                    page.add(&section);
+
+                    section
+                });
+
+                group.add(&adw::ActionRow::builder()
+                    .title(line)
+                    .subtitle(value)
+                    .subtitle_selectable(true)
+                    .css_classes(["property"])
+                    .build()
+                );
+            }
+
+            page
+        }
+
         //---------------------------------------
         // Open folder helper function
         //---------------------------------------
@@ -155,33 +191,221 @@ mod imp {
                 }
             );
 
+            // Show elevation error action
+            klass.install_action("win.show-elevation-error", Some(VariantTy::STRING),
+                |window, _, param| {
+                    let error = param.and_then(glib::Variant::get::<String>)
+                        .unwrap_or_default();
+
+                    let dialog = adw::AlertDialog::builder()
+                        .heading("Authentication Unavailable")
+                        .body(format!(
+                            "{}\n\nSettings can be viewed, but not changed. To use another elevation method, start the application with --elevation pkexec, run0 or sudo (or set the LG_GRAM_ELEVATION environment variable).",
+                            error.trim().trim_start_matches("ERROR: ")
+                        ))
+                        .default_response("close")
+                        .close_response("close")
+                        .build();
+
+                    dialog.add_responses(&[("close", "_Close")]);
+
+                    dialog.present(Some(window));
+                }
+            );
+
+            // Reload policy action
+            klass.install_action("win.reload-policy", None, |window, _, _| {
+                window.load_policy();
+            });
+
             // Show system information action async
             klass.install_action_async("win.show-system-info", None, async |window, _, _| {
-                match gram::system_information_async().await {
+                match window.backend().system_information(false).await {
                     Ok(info) => {
                         let builder = gtk::Builder::from_resource(
                             "/com/github/LGGramSettings/ui/builder/info_dialog.ui"
                         );
 
                         let info_dialog: adw::Dialog = builder.object("info_dialog").unwrap();
+                        let toolbar_view: adw::ToolbarView = builder.object("toolbar_view").unwrap();
+                        let reveal_button: gtk::ToggleButton = builder.object("reveal_button").unwrap();
+
+                        toolbar_view.set_content(Some(&Self::info_page(&info)));
+
+                        // Reveal button toggled signal
+                        reveal_button.connect_toggled(glib::clone!(
+                            #[weak] window,
+                            #[weak] toolbar_view,
+                            move |button| {
+                                // Show masked information
+                                if !button.is_active() {
+                                    toolbar_view.set_content(Some(&Self::info_page(&info)));
+                                    return
+                                }
+
+                                // Retrieve unmasked information
+                                glib::spawn_future_local(glib::clone!(
+                                    #[weak] window,
+                                    #[weak] toolbar_view,
+                                    #[weak] button,
+                                    async move {
+                                        match window.backend().system_information(true).await {
+                                            Ok(info) => {
+                                                if button.is_active() {
+                                                    toolbar_view.set_content(Some(&Self::info_page(&info)));
+                                                }
+                                            },
+                                            Err(error) => {
+                                                button.set_active(false);
+
+                                                window.show_error(&error);
+                                            }
+                                        }
+                                    }
+                                ));
+                            }
+                        ));
+
+                        info_dialog.present(Some(&window));
+                    },
+                    Err(error) => {
+                        window.show_error(&error);
+                    }
+                }
+            });
+
+            // Show self check action async
+            klass.install_action_async("win.show-self-check", None, async |window, _, _| {
+                match window.backend().self_check().await {
+                    Ok(report) => {
+                        let builder = gtk::Builder::from_resource(
+                            "/com/github/LGGramSettings/ui/builder/check_dialog.ui"
+                        );
+
+                        let check_dialog: adw::Dialog = builder.object("check_dialog").unwrap();
                         let group: adw::PreferencesGroup = builder.object("group").unwrap();
 
-                        let mut iter = info.split('\n');
+                        let mut lines = report.lines().peekable();
 
-                        while let Some((label, value)) = iter.next().zip(iter.next()) {
-                            group.add(&adw::ActionRow::builder()
-                                .title(label)
-                                .subtitle(value)
+                        while let Some(line) = lines.next() {
+                            let Some((status, check)) = line.split_once(' ') else {
+                                continue
+                            };
+
+                            let (name, detail) = check.split_once(": ").unwrap_or((check, ""));
+
+                            // Append fix to failed checks
+                            let fix = lines.next_if(|line| line.trim_start().starts_with("FIX "))
+                                .map(|line| line.trim_start().trim_start_matches("FIX ").to_owned());
+
+                            let subtitle = fix.map_or_else(|| detail.to_owned(), |fix| format!("{detail}\n{fix}"));
+
+                            let (icon_name, css_class) = if status == "PASS" {
+                                ("emblem-ok-symbolic", "success")
+                            } else {
+                                ("dialog-warning-symbolic", "error")
+                            };
+
+                            let row = adw::ActionRow::builder()
+                                .title(name)
+                                .subtitle(subtitle)
                                 .subtitle_selectable(true)
-                                .css_classes(["property"])
+                                .build();
+
+                            row.add_prefix(&gtk::Image::builder()
+                                .icon_name(icon_name)
+                                .css_classes([css_class])
                                 .build()
                             );
+
+                            group.add(&row);
                         }
 
-                        info_dialog.present(Some(&window));
+                        check_dialog.present(Some(&window));
+                    },
+                    Err(error) => {
+                        window.show_error(&error);
+                    }
+                }
+            });
+
+            // Show unit problems action
+            klass.install_action("win.show-unit-problems", None, |window, _, _| {
+                let Some(report) = window.imp().unit_problems.borrow().clone() else {
+                    return
+                };
+
+                let problems = report.lines()
+                    .filter(|line| !line.trim_start().starts_with("FIX "))
+                    .map(|line| format!("• {line}"))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                let dialog = adw::AlertDialog::builder()
+                    .heading("Startup Unit Problems")
+                    .body(format!("{problems}\n\nRepairing disables conflicting units and removes broken ones."))
+                    .default_response("repair")
+                    .close_response("cancel")
+                    .build();
+
+                dialog.add_responses(&[("cancel", "_Cancel"), ("repair", "_Repair")]);
+                dialog.set_response_appearance("repair", adw::ResponseAppearance::Suggested);
+
+                // Dialog repair response signal
+                dialog.connect_response(Some("repair"), glib::clone!(
+                    #[weak] window,
+                    move |_, _| {
+                        gtk::prelude::WidgetExt::activate_action(&window, "win.repair-units", None).unwrap();
+                    }
+                ));
+
+                dialog.present(Some(window));
+            });
+
+            // Repair units action async
+            klass.install_action_async("win.repair-units", None, async |window, _, _| {
+                match window.backend().repair_units().await {
+                    Ok(_) => {
+                        let imp = window.imp();
+
+                        imp.unit_problems.replace(None);
+                        imp.units_banner.set_revealed(false);
+
+                        imp.toast_overlay.add_toast(adw::Toast::new("Startup units repaired"));
+
+                        window.load_persisted_values();
                     },
                     Err(error) => {
-                        gtk::prelude::WidgetExt::activate_action(&window, "win.show-error-toast", Some(&error.to_variant())).unwrap();
+                        window.show_error(&error);
+                    }
+                }
+            });
+
+            // Save diagnostics action async
+            klass.install_action_async("win.save-diagnostics", None, async |window, _, _| {
+                let dialog = gtk::FileDialog::builder()
+                    .title("Save Diagnostics Bundle")
+                    .initial_name("lg-gram-diagnostics.tar.gz")
+                    .build();
+
+                let Ok(file) = dialog.save_future(Some(&window)).await else {
+                    return
+                };
+
+                let archive = match window.backend().diagnostics().await {
+                    Ok(archive) => archive,
+                    Err(error) => {
+                        window.show_error(&error);
+                        return
+                    }
+                };
+
+                match file.replace_contents_future(archive, None, false, gio::FileCreateFlags::REPLACE_DESTINATION).await {
+                    Ok(_) => {
+                        window.imp().toast_overlay.add_toast(adw::Toast::new("Diagnostics bundle saved"));
+                    },
+                    Err((_, error)) => {
+                        gtk::prelude::WidgetExt::activate_action(&window, "win.show-error-toast", Some(&error.to_string().to_variant())).unwrap();
                     }
                 }
             });
@@ -214,9 +438,51 @@ impl MainWindow {
     // New function
     //---------------------------------------
     pub fn new(app: &Application) -> Self {
-        glib::Object::builder()
+        Self::with_backend(app, Rc::new(gram::WriterBackend))
+    }
+
+    //---------------------------------------
+    // With backend function
+    //---------------------------------------
+    pub fn with_backend(app: &Application, backend: Rc<dyn gram::Backend>) -> Self {
+        let window: Self = glib::Object::builder()
             .property("application", app)
-            .build()
+            .build();
+
+        window.imp().backend.get_or_init(|| backend);
+
+        window.init_kernel_features();
+        window.check_units();
+
+        window
+    }
+
+    //---------------------------------------
+    // Backend helper function
+    //---------------------------------------
+    fn backend(&self) -> Rc<dyn gram::Backend> {
+        self.imp().backend.get().cloned()
+            .expect("Failed to get backend")
+    }
+
+    //---------------------------------------
+    // Setup widgets
+    //---------------------------------------
+    fn setup_widgets(&self) {
+        let imp = self.imp();
+
+        imp.apply_mode_dropdown.set_model(Some(&adw::EnumListModel::new(ApplyMode::static_type())));
+
+        // Bind apply mode to gram widgets
+        for widget in self.gram_widgets() {
+            imp.apply_mode_dropdown.bind_property("selected-item", &widget, "apply-mode")
+                .transform_to(|_, item: Option<adw::EnumListItem>| {
+                    glib::EnumClass::with_type(ApplyMode::static_type())?
+                        .to_value(item?.value())
+                })
+                .sync_create()
+                .build();
+        }
     }
 
     //---------------------------------------
@@ -225,9 +491,250 @@ impl MainWindow {
     fn init_kernel_features(&self) {
         let imp = self.imp();
 
-        imp.battery_limit_widget.init(BATTERY_LIMIT, BatteryCareLimit::static_type());
-        imp.fn_lock_widget.init(FN_LOCK, OnOff::static_type());
-        imp.usb_charge_widget.init(USB_CHARGE, OnOff::static_type());
-        imp.fan_mode_widget.init(FAN_MODE, FanMode::static_type());
+        let backend = self.backend();
+        let snapshot = gram::Snapshot::take(backend.as_ref());
+
+        imp.battery_limit_widget.init(gram::Feature::BatteryCareLimit, &backend, &snapshot);
+        imp.fn_lock_widget.init(gram::Feature::FnLock, &backend, &snapshot);
+        imp.usb_charge_widget.init(gram::Feature::UsbCharge, &backend, &snapshot);
+        imp.fan_mode_widget.init(gram::Feature::FanMode, &backend, &snapshot);
+
+        // Update widgets if values are changed outside the app
+        let watcher = gram::Watcher::new(backend, glib::clone!(
+            #[weak(rename_to = window)] self,
+            move |value| {
+                for widget in window.gram_widgets() {
+                    widget.update_value(value);
+                }
+            }
+        ));
+
+        // Poll only while window is focused
+        if self.is_active() {
+            watcher.start();
+        }
+
+        imp.watcher.replace(Some(watcher));
+
+        self.connect_is_active_notify(|window| {
+            if let Some(watcher) = window.imp().watcher.borrow().as_ref() {
+                if window.is_active() {
+                    watcher.start();
+                } else {
+                    watcher.stop();
+                }
+            }
+        });
+
+        self.load_persisted_values();
+        self.load_policy();
+    }
+
+    //---------------------------------------
+    // Load policy
+    //---------------------------------------
+    fn load_policy(&self) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = window)] self,
+            async move {
+                match window.backend().policy().await {
+                    Ok(rules) => {
+                        for widget in window.gram_widgets() {
+                            widget.set_policy(&rules);
+                        }
+                    },
+                    Err(error) => {
+                        window.show_error(&error);
+                    }
+                }
+            }
+        ));
+    }
+
+    //---------------------------------------
+    // Load persisted values
+    //---------------------------------------
+    fn load_persisted_values(&self) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = window)] self,
+            async move {
+                match window.backend().persisted_features().await {
+                    Ok(values) => {
+                        for widget in window.gram_widgets() {
+                            widget.set_persisted_values(&values);
+                        }
+                    },
+                    Err(error) => {
+                        window.show_error(&error);
+                    }
+                }
+            }
+        ));
+    }
+
+    //---------------------------------------
+    // Check units
+    //---------------------------------------
+    fn check_units(&self) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = window)] self,
+            async move {
+                // Errors are ignored, installation problems are reported by self check
+                if let Ok(Some(report)) = window.backend().unit_problems().await {
+                    let imp = window.imp();
+
+                    imp.unit_problems.replace(Some(report));
+                    imp.units_banner.set_revealed(true);
+                }
+            }
+        ));
+    }
+
+    //---------------------------------------
+    // Show error helper function
+    //---------------------------------------
+    fn show_error(&self, error: &gram::Error) {
+        match error {
+            // Cancelled by user, nothing to report
+            gram::Error::ElevationCancelled => {},
+            gram::Error::ElevationUnavailable(message) => {
+                gtk::prelude::WidgetExt::activate_action(self, "win.show-elevation-error", Some(&message.to_variant())).unwrap();
+            },
+            _ => {
+                gtk::prelude::WidgetExt::activate_action(self, "win.show-error-toast", Some(&error.to_string().to_variant())).unwrap();
+            }
+        }
+    }
+
+    //---------------------------------------
+    // Gram widgets helper function
+    //---------------------------------------
+    fn gram_widgets(&self) -> [GramWidget; 4] {
+        let imp = self.imp();
+
+        [
+            imp.battery_limit_widget.get(),
+            imp.fn_lock_widget.get(),
+            imp.usb_charge_widget.get(),
+            imp.fan_mode_widget.get(),
+        ]
+    }
+
+    //---------------------------------------
+    // Refresh kernel features
+    //---------------------------------------
+    fn refresh_kernel_features(&self) {
+        let snapshot = gram::Snapshot::take(self.backend().as_ref());
+
+        for widget in self.gram_widgets() {
+            widget.update_from_snapshot(&snapshot);
+        }
+    }
+
+    //---------------------------------------
+    // Setup signals
+    //---------------------------------------
+    fn setup_signals(&self) {
+        let Ok(connection) = gio::bus_get_sync(gio::BusType::System, None::<&gio::Cancellable>) else {
+            return
+        };
+
+        // Logind prepare for sleep signal (refresh features after resume)
+        let subscription = connection.subscribe_to_signal(
+            Some("org.freedesktop.login1"),
+            Some("org.freedesktop.login1.Manager"),
+            Some("PrepareForSleep"),
+            Some("/org/freedesktop/login1"),
+            None,
+            gio::DBusSignalFlags::NONE,
+            glib::clone!(
+                #[weak(rename_to = window)] self,
+                move |signal| {
+                    if signal.parameters.get::<(bool,)>() == Some((false,)) {
+                        // Give resume hook time to reapply settings
+                        glib::timeout_add_seconds_local_once(RESUME_DELAY, glib::clone!(
+                            #[weak] window,
+                            move || {
+                                window.refresh_kernel_features();
+                            }
+                        ));
+                    }
+                }
+            )
+        );
+
+        self.imp().sleep_subscription.replace(Some(subscription));
+    }
+}
+
+//------------------------------------------------------------------------------
+// TESTS
+//------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use super::*;
+
+    static RESOURCES: Once = Once::new();
+
+    //---------------------------------------
+    // Window helper function
+    //---------------------------------------
+    // Main window with in-memory backend, in a registered (non-unique) application
+    fn window() -> MainWindow {
+        adw::init().expect("Failed to initialize libadwaita");
+
+        RESOURCES.call_once(|| {
+            gio::resources_register_include!("resources.gresource")
+                .expect("Failed to register resources");
+        });
+
+        let app = Application::new(crate::APP_ID, gio::ApplicationFlags::NON_UNIQUE);
+
+        app.register(None::<&gio::Cancellable>)
+            .expect("Failed to register application");
+
+        MainWindow::with_backend(&app, Rc::new(gram::MemoryBackend::default()))
+    }
+
+    //---------------------------------------
+    // Alert dialog helper function
+    //---------------------------------------
+    fn alert_dialog(window: &MainWindow) -> Option<adw::AlertDialog> {
+        window.visible_dialog()
+            .and_downcast::<adw::AlertDialog>()
+    }
+
+    #[gtk::test]
+    fn unavailable_elevation_shows_elevation_dialog() {
+        let window = window();
+
+        window.show_error(&gram::Error::ElevationUnavailable(String::from("ERROR: No elevation method available")));
+
+        let dialog = alert_dialog(&window)
+            .expect("Failed to get elevation error dialog");
+
+        assert_eq!(dialog.heading().as_deref(), Some("Authentication Unavailable"));
+        assert!(dialog.body().starts_with("No elevation method available"));
+        assert!(dialog.body().contains("--elevation"));
+    }
+
+    #[gtk::test]
+    fn writer_error_shows_toast_instead_of_dialog() {
+        let window = window();
+
+        window.show_error(&gram::Error::WriterFailed(gram::WriterCategory::Other, String::from("ERROR: Simulated write failure")));
+
+        assert!(alert_dialog(&window).is_none());
+    }
+
+    #[gtk::test]
+    fn cancelled_elevation_shows_nothing() {
+        let window = window();
+
+        window.show_error(&gram::Error::ElevationCancelled);
+
+        assert!(alert_dialog(&window).is_none());
     }
 }
@@ -0,0 +1,84 @@
+use std::fs;
+
+use nix::unistd::Group;
+
+use crate::persistence::exists;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const RULES_PATH: &str = "/etc/polkit-1/rules.d";
+const RULES_FILE: &str = "/etc/polkit-1/rules.d/49-lg-gram-settings.rules";
+
+const GROUP_MARKER: &str = "// Group: ";
+
+//---------------------------------------
+// Group access function
+//---------------------------------------
+pub fn group() -> Option<String> {
+    fs::read_to_string(RULES_FILE).ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix(GROUP_MARKER))
+        .map(|group| group.trim().to_owned())
+}
+
+//---------------------------------------
+// Rule helper function
+//---------------------------------------
+fn rule(group: &str) -> String {
+    // Feature changes only, reset and policy operations (lg.gram.manage) still require admin
+    format!(r#"// Managed by lg-gram-writer, use 'lg-gram-writer group-access' to change
+{GROUP_MARKER}{group}
+polkit.addRule(function(action, subject) {{
+    if (subject.local && subject.active && subject.isInGroup("{group}") &&
+        (action.id == "lg.gram.writer.execute" || /^lg\.gram\.[a-z-]+\.set$/.test(action.id))) {{
+        return polkit.Result.YES;
+    }}
+}});
+"#)
+}
+
+//---------------------------------------
+// Enable group access function
+//---------------------------------------
+pub fn enable(group: &str) -> Result<String, String> {
+    // Group name is embedded in JavaScript rule
+    let valid = !group.is_empty() && group.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+
+    if !valid {
+        return Err(format!("ERROR: Invalid group name {group}"))
+    }
+
+    if !matches!(Group::from_name(group), Ok(Some(_))) {
+        return Err(format!("ERROR: Group {group} does not exist (create it with: groupadd {group})"))
+    }
+
+    fs::create_dir_all(RULES_PATH)
+        .and_then(|()| fs::write(RULES_FILE, rule(group)))
+        .map_err(|_| format!("ERROR: Error writing to {RULES_FILE}"))?;
+
+    Ok(format!("Members of group {group} can now change settings without authentication"))
+}
+
+//---------------------------------------
+// Disable group access function
+//---------------------------------------
+pub fn disable() -> Result<String, String> {
+    if exists(RULES_FILE) {
+        fs::remove_file(RULES_FILE)
+            .map_err(|_| format!("ERROR: Error removing {RULES_FILE}"))?;
+    }
+
+    Ok(String::from("Passwordless group access disabled"))
+}
+
+//---------------------------------------
+// Status function
+//---------------------------------------
+pub fn status() -> Result<String, String> {
+    Ok(group().map_or_else(
+        || String::from("Passwordless group access disabled"),
+        |group| format!("Passwordless group access enabled for group {group}")
+    ))
+}
@@ -0,0 +1,159 @@
+use std::env;
+use std::fs;
+use std::os::unix::process::parent_id;
+use std::process;
+use std::sync::Mutex;
+
+use crate::cli::Mode;
+use crate::error::{Category, Error};
+use crate::features::FEATURES;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const MANAGE_ACTION: &str = "lg.gram.manage";
+
+// Feature change actions already authorized in this session (None outside of session)
+static SESSION_ACTIONS: Mutex<Option<Vec<&'static str>>> = Mutex::new(None);
+
+//------------------------------------------------------------------------------
+// STRUCT: Authorization
+//------------------------------------------------------------------------------
+pub struct Authorization {
+    pub action: &'static str,
+    pub details: Vec<(&'static str, String)>,
+}
+
+impl Authorization {
+    //---------------------------------------
+    // New function
+    //---------------------------------------
+    fn new(action: &'static str, details: &[(&'static str, &str)]) -> Self {
+        Self {
+            action,
+            details: details.iter().map(|&(key, value)| (key, value.to_owned())).collect(),
+        }
+    }
+
+    //---------------------------------------
+    // Description function
+    //---------------------------------------
+    fn description(&self) -> String {
+        let detail = |key: &str| {
+            self.details.iter()
+                .find(|(other, _)| *other == key)
+                .map(|(_, value)| value.as_str())
+        };
+
+        match (detail("setting"), detail("value"), detail("operation")) {
+            (Some(setting), Some(value), _) => format!("change {setting} to {value}"),
+            (_, _, Some(operation)) => operation.to_owned(),
+            _ => self.action.to_owned()
+        }
+    }
+}
+
+//---------------------------------------
+// Authorization for mode function
+//---------------------------------------
+pub fn authorization(mode: &Mode) -> Option<Authorization> {
+    let authorization = match mode {
+        Mode::Get { .. } | Mode::Status | Mode::SelfCheck
+            | Mode::Repair { check: true } | Mode::Policy { apply: false }
+            | Mode::GroupAccess { enable: None, disable: false } => return None,
+        // Each session request is authorized when it is served
+        Mode::Session => return None,
+        Mode::Set { feature, value, .. } => {
+            Authorization::new(feature.action, &[("setting", feature.name), ("value", feature.label(value))])
+        },
+        Mode::Revert { feature } => {
+            Authorization::new(feature.action, &[("setting", feature.name), ("value", "its persisted value")])
+        },
+        Mode::SystemInfo { reveal: false } => Authorization::new("lg.gram.system-info.read", &[]),
+        Mode::SystemInfo { reveal: true } => Authorization::new("lg.gram.system-info.reveal", &[]),
+        Mode::Diagnostics => Authorization::new("lg.gram.diagnostics.create", &[]),
+        Mode::Reset { .. } => Authorization::new(MANAGE_ACTION, &[("operation", "reset settings")]),
+        Mode::Reapply { .. } => Authorization::new(MANAGE_ACTION, &[("operation", "reapply settings")]),
+        Mode::Enforce { .. } => Authorization::new(MANAGE_ACTION, &[("operation", "change enforced settings")]),
+        Mode::Watch => Authorization::new(MANAGE_ACTION, &[("operation", "watch settings")]),
+        Mode::Repair { .. } => Authorization::new(MANAGE_ACTION, &[("operation", "repair startup units")]),
+        Mode::Policy { .. } => Authorization::new(MANAGE_ACTION, &[("operation", "apply administrator policy")]),
+        Mode::GroupAccess { .. } => Authorization::new(MANAGE_ACTION, &[("operation", "change passwordless group access")]),
+    };
+
+    Some(authorization)
+}
+
+//---------------------------------------
+// Process start time helper function
+//---------------------------------------
+fn start_time(pid: u32) -> Option<String> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // Skip command name, which may contain spaces
+    stat.rsplit_once(')')?.1
+        .split_whitespace()
+        .nth(19)
+        .map(ToOwned::to_owned)
+}
+
+//---------------------------------------
+// Start session function
+//---------------------------------------
+// Feature changes are authorized once per session, so that later changes do not prompt again
+// when the polkit keep window expires (other operations are authorized on every request)
+pub fn start_session() {
+    *SESSION_ACTIONS.lock().unwrap() = Some(Vec::new());
+}
+
+//---------------------------------------
+// Authorize function
+//---------------------------------------
+pub fn authorize(authorization: &Authorization) -> Result<(), Error> {
+    // Only callers elevated by pkexec need authorization (root, sudo and run0 are trusted)
+    let Ok(uid) = env::var("PKEXEC_UID") else {
+        return Ok(())
+    };
+
+    let is_feature_action = FEATURES.iter().any(|feature| feature.action == authorization.action);
+
+    let mut session_actions = SESSION_ACTIONS.lock().unwrap();
+
+    if is_feature_action && session_actions.as_ref().is_some_and(|actions| actions.contains(&authorization.action)) {
+        return Ok(())
+    }
+
+    // Subject is the process that called pkexec
+    let pid = parent_id();
+
+    let start_time = start_time(pid)
+        .ok_or_else(|| String::from("ERROR: Failed to identify calling process"))?;
+
+    let mut command = process::Command::new("pkcheck");
+
+    command.arg("--action-id").arg(authorization.action)
+        .arg("--process").arg(format!("{pid},{start_time},{uid}"))
+        .arg("--allow-user-interaction");
+
+    for (key, value) in &authorization.details {
+        command.arg("--detail").arg(key).arg(value);
+    }
+
+    let output = command.output()
+        .map_err(|error| format!("ERROR: Failed to run pkcheck: {error}"))?;
+
+    match output.status.code() {
+        Some(0) => {
+            if let Some(actions) = session_actions.as_mut() && is_feature_action {
+                actions.push(authorization.action);
+            }
+
+            Ok(())
+        },
+        Some(3) => Err(Error::new(Category::Dismissed, format!("ERROR: Authorization to {} was dismissed", authorization.description()))),
+        Some(1) => Err(Error::new(Category::NotAuthorized, format!("ERROR: Not authorized to {}", authorization.description()))),
+        // Authentication would be required, but no polkit agent is running
+        Some(2) => Err(Error::new(Category::NoAgent, format!("ERROR: Not authorized to {} (no authentication agent available)", authorization.description()))),
+        _ => Err(format!("ERROR: Failed to check authorization for {}", authorization.action).into())
+    }
+}
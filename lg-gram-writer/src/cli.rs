@@ -0,0 +1,274 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+
+use crate::features::{self, Feature, FEATURES};
+use crate::overrides;
+use crate::persistence::BACKEND_NAMES;
+use crate::policy::POLICY_FILE;
+
+//------------------------------------------------------------------------------
+// ENUM: Mode
+//------------------------------------------------------------------------------
+pub enum Mode {
+    Get { feature: Option<&'static Feature>, persisted: bool },
+    Set { feature: &'static Feature, value: &'static str, backend: Option<&'static str>, runtime: bool, duration: Option<u64> },
+    Revert { feature: &'static Feature },
+    Status,
+    SystemInfo { reveal: bool },
+    Reset { feature: Option<&'static Feature> },
+    Reapply { feature: Option<&'static Feature>, wait: bool },
+    Enforce { feature: &'static Feature, enabled: bool },
+    Watch,
+    Repair { check: bool },
+    Policy { apply: bool },
+    GroupAccess { enable: Option<String>, disable: bool },
+    Diagnostics,
+    SelfCheck,
+    Session
+}
+
+impl Mode {
+    //---------------------------------------
+    // Requires root function
+    //---------------------------------------
+    pub fn requires_root(&self) -> bool {
+        !matches!(self, Self::Get { .. } | Self::Status | Self::SelfCheck | Self::Repair { check: true } | Self::Policy { apply: false }
+            | Self::GroupAccess { enable: None, disable: false })
+    }
+}
+
+//---------------------------------------
+// Feature argument helper function
+//---------------------------------------
+fn feature_arg(help: &'static str) -> Arg {
+    Arg::new("feature")
+        .value_name("FEATURE")
+        .help(help)
+        .value_parser(PossibleValuesParser::new(
+            FEATURES.iter().map(|feature| PossibleValue::new(feature.id).help(feature.name))
+        ))
+}
+
+//---------------------------------------
+// Feature helper function
+//---------------------------------------
+fn feature(matches: &ArgMatches) -> Option<&'static Feature> {
+    matches.get_one::<String>("feature")
+        .and_then(|id| features::feature(id))
+}
+
+//---------------------------------------
+// Command function
+//---------------------------------------
+pub fn command() -> Command {
+    // Set subcommand with one subcommand per feature
+    let set_command = Command::new("set")
+        .about("Change a feature value and persist it across reboots")
+        .subcommand_required(true)
+        .subcommand_value_name("FEATURE")
+        .subcommand_help_heading("Features")
+        .disable_help_subcommand(true)
+        .arg(Arg::new("backend")
+            .long("backend")
+            .value_name("NAME")
+            .global(true)
+            .help("Persistence backend (default: backend already used for the feature, or detected from init system)")
+            .long_help("Persistence backend (default: backend already used for the feature, or detected from init system)\n\n\
+                The feature is removed from all other backends, including enabled systemd units. \
+                With any backend, the lg_gram_resume.service unit is also enabled when systemd is running, \
+                so that persisted values are reapplied after suspend.")
+            .value_parser(PossibleValuesParser::new(BACKEND_NAMES))
+        )
+        .arg(Arg::new("runtime")
+            .long("runtime")
+            .global(true)
+            .action(ArgAction::SetTrue)
+            .conflicts_with("backend")
+            .help("Change live value only, without persistence (until reboot)")
+        )
+        .arg(Arg::new("for")
+            .long("for")
+            .value_name("DURATION")
+            .global(true)
+            .conflicts_with_all(["backend", "runtime"])
+            .value_parser(overrides::parse_duration)
+            .help("Change live value temporarily, reverting to the persisted value after DURATION (e.g. 30m, 2h)")
+        )
+        .subcommands(FEATURES.iter().map(|feature| {
+            Command::new(feature.id)
+                .about(feature.name)
+                .arg(Arg::new("value")
+                    .value_name("VALUE")
+                    .required(true)
+                    .value_parser(PossibleValuesParser::new(
+                        feature.values.iter().map(|&(value, label)| PossibleValue::new(value).help(label))
+                    ))
+                )
+        }));
+
+    Command::new("lg-gram-writer")
+        .about("Read and change LG Gram laptop kernel features")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(Command::new("get")
+            .about("Print current feature values")
+            .arg(feature_arg("Feature to print (default: all features)"))
+            .arg(Arg::new("persisted")
+                .long("persisted")
+                .action(ArgAction::SetTrue)
+                .help("Print values applied at boot instead of live values")
+            )
+        )
+        .subcommand(set_command)
+        .subcommand(Command::new("status")
+            .about("Print current and persisted feature values")
+        )
+        .subcommand(Command::new("system-info")
+            .about("Print system, BIOS, battery and driver information")
+            .arg(Arg::new("reveal")
+                .long("reveal")
+                .action(ArgAction::SetTrue)
+                .help("Do not mask identifying fields (serial number, UUID, asset tag)")
+            )
+        )
+        .subcommand(Command::new("revert")
+            .about("End a temporary override and restore the persisted value")
+            .arg(feature_arg("Feature to revert").required(true))
+        )
+        .subcommand(Command::new("reset")
+            .about("Restore default feature values and remove persistence")
+            .arg(feature_arg("Feature to reset (default: all features)"))
+        )
+        .subcommand(Command::new("reapply")
+            .about("Reapply persisted feature values (run after resume)")
+            .arg(feature_arg("Feature to reapply (default: all features)"))
+            .arg(Arg::new("wait")
+                .long("wait")
+                .action(ArgAction::SetTrue)
+                .help("Wait for setting files to appear (run when lg-laptop device is added)")
+            )
+        )
+        .subcommand(Command::new("enforce")
+            .about("Restore the persisted value of a feature whenever firmware changes it")
+            .arg(feature_arg("Feature to enforce").required(true))
+            .arg(Arg::new("disable")
+                .long("disable")
+                .action(ArgAction::SetTrue)
+                .help("Stop enforcing the feature")
+            )
+        )
+        .subcommand(Command::new("watch")
+            .about("Watch enforced features and restore drifted values (run as a service)")
+        )
+        .subcommand(Command::new("repair")
+            .about("Fix conflicting, orphaned, masked or dangling lg_gram units")
+            .arg(Arg::new("check")
+                .long("check")
+                .action(ArgAction::SetTrue)
+                .help("Only report problems, without fixing them")
+            )
+        )
+        .subcommand(Command::new("policy")
+            .about(format!("Print administrator policy from {POLICY_FILE}"))
+            .arg(Arg::new("apply")
+                .long("apply")
+                .action(ArgAction::SetTrue)
+                .help("Apply and persist pinned values")
+            )
+        )
+        .subcommand(Command::new("group-access")
+            .about("Allow members of a group to change features without authentication (print status if no option is given)")
+            .arg(Arg::new("enable")
+                .long("enable")
+                .value_name("GROUP")
+                .num_args(0..=1)
+                .default_missing_value("lggram")
+                .help("Install polkit rule for GROUP (default: lggram)")
+            )
+            .arg(Arg::new("disable")
+                .long("disable")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("enable")
+                .help("Remove polkit rule")
+            )
+        )
+        .subcommand(Command::new("diagnostics")
+            .about("Write a diagnostics bundle (tar.gz) for bug reports to standard output, with identifying fields masked")
+        )
+        .subcommand(Command::new("self-check")
+            .about("Check installation of writer, polkit policy, unit files, pkexec and driver")
+        )
+        .subcommand(Command::new("session")
+            .about("Serve requests from standard input until it is closed or idle (used by the GUI)")
+        )
+}
+
+//---------------------------------------
+// Parse function
+//---------------------------------------
+pub fn parse() -> Mode {
+    mode(&command().get_matches())
+}
+
+//---------------------------------------
+// Try parse from function
+//---------------------------------------
+pub fn try_parse_from<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<Mode, String> {
+    let matches = command()
+        .try_get_matches_from(["lg-gram-writer"].into_iter().chain(args))
+        .map_err(|error| format!("ERROR: Invalid request ({})", error.kind()))?;
+
+    Ok(mode(&matches))
+}
+
+//---------------------------------------
+// Mode helper function
+//---------------------------------------
+fn mode(matches: &ArgMatches) -> Mode {
+    match matches.subcommand() {
+        Some(("get", matches)) => Mode::Get { feature: feature(matches), persisted: matches.get_flag("persisted") },
+        Some(("set", matches)) => {
+            let (id, matches) = matches.subcommand()
+                .expect("Failed to get feature subcommand");
+
+            let feature = features::feature(id)
+                .expect("Failed to get feature");
+
+            let value = matches.get_one::<String>("value")
+                .and_then(|value| feature.value(value))
+                .expect("Failed to get feature value");
+
+            let backend = matches.get_one::<String>("backend")
+                .and_then(|name| BACKEND_NAMES.into_iter().find(|backend| backend == name));
+
+            Mode::Set {
+                feature,
+                value,
+                backend,
+                runtime: matches.get_flag("runtime"),
+                duration: matches.get_one::<u64>("for").copied()
+            }
+        },
+        Some(("status", _)) => Mode::Status,
+        Some(("system-info", matches)) => Mode::SystemInfo { reveal: matches.get_flag("reveal") },
+        Some(("revert", matches)) => Mode::Revert { feature: feature(matches).expect("Failed to get feature") },
+        Some(("reset", matches)) => Mode::Reset { feature: feature(matches) },
+        Some(("reapply", matches)) => Mode::Reapply { feature: feature(matches), wait: matches.get_flag("wait") },
+        Some(("enforce", matches)) => Mode::Enforce {
+            feature: feature(matches).expect("Failed to get feature"),
+            enabled: !matches.get_flag("disable")
+        },
+        Some(("watch", _)) => Mode::Watch,
+        Some(("repair", matches)) => Mode::Repair { check: matches.get_flag("check") },
+        Some(("policy", matches)) => Mode::Policy { apply: matches.get_flag("apply") },
+        Some(("group-access", matches)) => Mode::GroupAccess {
+            enable: matches.get_one::<String>("enable").cloned(),
+            disable: matches.get_flag("disable")
+        },
+        Some(("self-check", _)) => Mode::SelfCheck,
+        Some(("diagnostics", _)) => Mode::Diagnostics,
+        Some(("session", _)) => Mode::Session,
+        _ => unreachable!()
+    }
+}
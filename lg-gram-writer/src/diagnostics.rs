@@ -0,0 +1,185 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::process;
+
+use glob::glob;
+
+use crate::{SETTINGS_PATH, MODULE_PATH, POWER_SUPPLY_PATH, status};
+use crate::persistence::ENABLED_PATH;
+use crate::sysinfo;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const LEDS_PATH: &str = "/sys/class/leds";
+
+//---------------------------------------
+// Command output helper function
+//---------------------------------------
+fn command_output(program: &str, args: &[&str]) -> String {
+    let header = format!("$ {program} {}\n", args.join(" "));
+
+    match process::Command::new(program).args(args).output() {
+        Ok(output) => {
+            format!("{header}{}{}\n",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            )
+        },
+        Err(error) => format!("{header}ERROR: {error}\n\n")
+    }
+}
+
+//---------------------------------------
+// Attribute files helper function
+//---------------------------------------
+fn attribute_files(dir: &Path) -> String {
+    let mut files: Vec<_> = fs::read_dir(dir).into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+
+    files.sort();
+
+    files.iter()
+        .map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            let value = fs::read_to_string(path)
+                .map_or_else(|error| format!("<{error}>"), |value| value.trim().to_owned());
+
+            // Mask identifying attributes
+            let value = if name == "serial_number" { sysinfo::mask(&value) } else { value };
+
+            format!("{name}={value}\n")
+        })
+        .collect()
+}
+
+//---------------------------------------
+// Attributes report function
+//---------------------------------------
+fn attributes_report() -> String {
+    let mut report = format!("[{SETTINGS_PATH}]\n{}\n", attribute_files(Path::new(SETTINGS_PATH)));
+
+    // LEDs provided by lg-laptop driver
+    for led in fs::read_dir(LEDS_PATH).into_iter().flatten().flatten() {
+        let driver = fs::canonicalize(led.path().join("device"))
+            .map(|path| path.to_string_lossy().contains("lg-laptop"))
+            .unwrap_or_default();
+
+        if driver {
+            report.push_str(&format!("[{}]\n{}\n", led.path().display(), attribute_files(&led.path())));
+        }
+    }
+
+    // Power supplies
+    for supply in fs::read_dir(POWER_SUPPLY_PATH).into_iter().flatten().flatten() {
+        report.push_str(&format!("[{}]\n{}\n", supply.path().display(), attribute_files(&supply.path())));
+    }
+
+    report
+}
+
+//---------------------------------------
+// Units report function
+//---------------------------------------
+fn units_report() -> String {
+    let mut report = String::from("[persistence]\n");
+
+    report.push_str(&status().unwrap_or_else(|error| error));
+    report.push_str("\n\n[symlinks]\n");
+
+    for path in glob(&format!("{ENABLED_PATH}/**/lg_gram_*")).into_iter().flatten().flatten() {
+        let target = fs::read_link(&path)
+            .map_or_else(|error| format!("<{error}>"), |target| target.display().to_string());
+
+        report.push_str(&format!("{} -> {target}\n", path.display()));
+    }
+
+    report.push('\n');
+    report.push_str(&command_output("systemctl", &["list-unit-files", "--no-pager", "lg_gram_*"]));
+    report.push_str(&command_output("systemctl", &["status", "--no-pager", "--all", "lg_gram_*"]));
+
+    report
+}
+
+//---------------------------------------
+// Kernel report function
+//---------------------------------------
+fn kernel_report() -> String {
+    let mut report = command_output("uname", &["-srvm"]);
+
+    report.push_str(&format!("[{MODULE_PATH}]\n{}\n", attribute_files(Path::new(MODULE_PATH))));
+    report.push_str(&format!("[{MODULE_PATH}/parameters]\n{}\n", attribute_files(&Path::new(MODULE_PATH).join("parameters"))));
+    report.push_str(&command_output("modinfo", &["lg_laptop"]));
+
+    report
+}
+
+//---------------------------------------
+// Journal report function
+//---------------------------------------
+fn journal_report() -> String {
+    let mut report = command_output("journalctl", &["-b", "-k", "--no-pager", "--grep", "lg[-_]laptop"]);
+
+    report.push_str(&command_output("journalctl", &["-b", "--no-pager", "--grep", "lg[-_]gram"]));
+
+    report
+}
+
+//---------------------------------------
+// Diagnostics function
+//---------------------------------------
+// Archive is only written to stdout, so that no file is created or chowned with root privileges
+pub fn diagnostics() -> Result<String, String> {
+    // Create private temporary directory
+    let dir = env::temp_dir().join(format!("lg-gram-diagnostics-{}", process::id()));
+
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .map_err(|error| format!("ERROR: Failed to create temporary directory: {error}"))?;
+
+    let reports = [
+        ("attributes.txt", attributes_report()),
+        ("system-info.txt", sysinfo::system_information(false).unwrap_or_else(|error| error)),
+        ("units.txt", units_report()),
+        ("kernel.txt", kernel_report()),
+        ("journal.txt", journal_report()),
+    ];
+
+    let result = reports.iter()
+        .try_for_each(|(name, report)| fs::write(dir.join(name), report))
+        .map_err(|error| format!("ERROR: Failed to write report: {error}"))
+        .and_then(|()| {
+            // Create archive
+            let archive = process::Command::new("tar")
+                .args(["-czf", "-"])
+                .arg("-C")
+                .arg(&dir)
+                .args(reports.map(|(name, _)| name))
+                .output()
+                .map_err(|error| error.to_string())?;
+
+            if !archive.status.success() {
+                return Err(String::from_utf8_lossy(&archive.stderr).into())
+            }
+
+            Ok(archive.stdout)
+        });
+
+    let _ = fs::remove_dir_all(&dir);
+
+    let archive = result?;
+
+    io::stdout().write_all(&archive)
+        .map_err(|error| format!("ERROR: Failed to write diagnostics bundle: {error}"))?;
+
+    Ok(String::new())
+}
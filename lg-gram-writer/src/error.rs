@@ -0,0 +1,52 @@
+use std::fmt;
+
+//------------------------------------------------------------------------------
+// ENUM: Category
+//------------------------------------------------------------------------------
+// Used as exit code (and in session responses), so that callers need not parse messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Other = 1,
+    NoAgent = 2,
+    NotAuthorized = 3,
+    Dismissed = 4,
+    Policy = 5,
+    Busy = 6,
+}
+
+//------------------------------------------------------------------------------
+// STRUCT: Error
+//------------------------------------------------------------------------------
+#[derive(Debug)]
+pub struct Error {
+    pub category: Category,
+    pub message: String,
+}
+
+impl Error {
+    //---------------------------------------
+    // New function
+    //---------------------------------------
+    pub fn new(category: Category, message: impl Into<String>) -> Self {
+        Self { category, message: message.into() }
+    }
+
+    //---------------------------------------
+    // Exit code function
+    //---------------------------------------
+    pub fn code(&self) -> i32 {
+        self.category as i32
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::new(Category::Other, message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
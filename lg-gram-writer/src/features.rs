@@ -0,0 +1,159 @@
+use std::fs;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+use crate::SETTINGS_PATH;
+use crate::error::{Category, Error};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const WRITE_ATTEMPTS: u32 = 5;
+const WRITE_BACKOFF: Duration = Duration::from_millis(50);
+
+// Errors reported by the embedded controller when busy, worth retrying
+const TRANSIENT_ERRORS: [Errno; 4] = [Errno::EBUSY, Errno::EIO, Errno::EAGAIN, Errno::ETIMEDOUT];
+
+//------------------------------------------------------------------------------
+// STRUCT: Feature
+//------------------------------------------------------------------------------
+pub struct Feature {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub values: &'static [(&'static str, &'static str)],
+    pub default: &'static str,
+    pub action: &'static str,
+}
+
+impl Feature {
+    //---------------------------------------
+    // Settings file function
+    //---------------------------------------
+    pub fn settings_file(&self) -> String {
+        format!("{SETTINGS_PATH}/{}", self.id)
+    }
+
+    //---------------------------------------
+    // Value function
+    //---------------------------------------
+    pub fn value(&self, value: &str) -> Option<&'static str> {
+        self.values.iter()
+            .find(|(id, _)| *id == value)
+            .map(|(id, _)| *id)
+    }
+
+    //---------------------------------------
+    // Label function
+    //---------------------------------------
+    pub fn label<'a>(&self, value: &'a str) -> &'a str {
+        self.values.iter()
+            .find(|(id, _)| *id == value)
+            .map_or(value, |(_, label)| label)
+    }
+
+    //---------------------------------------
+    // Read function
+    //---------------------------------------
+    pub fn read(&self) -> Result<String, String> {
+        let settings_file = self.settings_file();
+
+        fs::metadata(&settings_file)
+            .map_err(|_| format!("ERROR: {} setting file not found", self.id))?;
+
+        fs::read_to_string(settings_file)
+            .map_err(|_| format!("ERROR: Error reading {} setting file", self.id))
+            .map(|value| value.trim().to_owned())
+    }
+
+    //---------------------------------------
+    // Write function
+    //---------------------------------------
+    pub fn write(&self, value: &str) -> Result<(), Error> {
+        let mut backoff = WRITE_BACKOFF;
+        let mut attempt = 1;
+
+        // Retry transient errors with exponential backoff
+        loop {
+            let error = match fs::write(self.settings_file(), format!("{value}\n")) {
+                Ok(()) => return Ok(()),
+                Err(error) => error
+            };
+
+            let errno = error.raw_os_error().map(Errno::from_raw);
+            let transient = errno.is_some_and(|errno| TRANSIENT_ERRORS.contains(&errno));
+
+            if transient && attempt < WRITE_ATTEMPTS {
+                thread::sleep(backoff);
+
+                backoff *= 2;
+                attempt += 1;
+
+                continue
+            }
+
+            return Err(Self::write_error(self.id, &error, errno, transient, attempt))
+        }
+    }
+
+    //---------------------------------------
+    // Write error helper function
+    //---------------------------------------
+    fn write_error(id: &str, error: &io::Error, errno: Option<Errno>, transient: bool, attempts: u32) -> Error {
+        let Some(errno) = errno else {
+            return format!("ERROR: Error writing to {id} setting file: {error}").into()
+        };
+
+        let (category, hint) = if transient {
+            (Category::Busy, format!("failed after {attempts} attempts, the embedded controller is busy, retrying later may help"))
+        } else {
+            (Category::Other, String::from("retrying will not help"))
+        };
+
+        Error::new(category, format!("ERROR: Error writing to {id} setting file: {} ({errno:?}, {hint})", errno.desc()))
+    }
+}
+
+//------------------------------------------------------------------------------
+// STATICS
+//------------------------------------------------------------------------------
+pub static FEATURES: [Feature; 4] = [
+    Feature {
+        id: "battery_care_limit",
+        name: "Battery care limit",
+        values: &[("100", "No limit"), ("80", "Limit to 80%")],
+        default: "100",
+        action: "lg.gram.battery-limit.set",
+    },
+    Feature {
+        id: "fn_lock",
+        name: "Fn lock",
+        values: &[("0", "Disabled"), ("1", "Enabled")],
+        default: "0",
+        action: "lg.gram.fn-lock.set",
+    },
+    Feature {
+        id: "usb_charge",
+        name: "USB charge",
+        values: &[("0", "Disabled"), ("1", "Enabled")],
+        default: "0",
+        action: "lg.gram.usb-charge.set",
+    },
+    Feature {
+        id: "fan_mode",
+        name: "Fan mode",
+        values: &[("0", "Optimized"), ("1", "Silent"), ("2", "Performance")],
+        default: "0",
+        action: "lg.gram.fan-mode.set",
+    },
+];
+
+//---------------------------------------
+// Feature by id function
+//---------------------------------------
+pub fn feature(id: &str) -> Option<&'static Feature> {
+    FEATURES.iter()
+        .find(|feature| feature.id == id)
+}
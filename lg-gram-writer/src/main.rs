@@ -1,86 +1,145 @@
-use std::env;
 use std::process;
-use std::fs;
-use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
-use glob::glob;
+mod access;
+mod authorize;
+mod cli;
+mod diagnostics;
+mod error;
+mod features;
+mod overrides;
+mod persistence;
+mod policy;
+mod repair;
+mod resume;
+mod selfcheck;
+mod session;
+mod sysinfo;
+mod watch;
+
+use cli::Mode;
+use error::Error;
+use features::{Feature, FEATURES};
+use policy::Policy;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const WRITER: &str = "/usr/share/lg-gram-settings/lg-gram-writer";
+const SETTINGS_PATH: &str = "/sys/devices/platform/lg-laptop";
+const MODULE_PATH: &str = "/sys/module/lg_laptop";
+const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+const REAPPLY_WAIT_ATTEMPTS: u32 = 50;
+const REAPPLY_WAIT_INTERVAL: Duration = Duration::from_millis(100);
 
 //------------------------------------------------------------------------------
 // APP: main
 //------------------------------------------------------------------------------
 fn main() {
+    // Parse args
+    let mode = cli::parse();
+
     // Exit if not running as root
-    if !nix::unistd::geteuid().is_root() {
+    if mode.requires_root() && !nix::unistd::geteuid().is_root() {
         eprintln!("ERROR: App must be run as root");
         process::exit(1);
     }
 
-    // Validate args
-    let args: Vec<String> = env::args().collect();
-
-    let Ok((mode, setting, value, enable)) = validate_args(&args) else {
-        eprint_usage(&args[0]);
-        process::exit(1);
-    };
-
-    // Check mode
-    let result = match mode {
-        "--system-info" => system_information(),
-        "--feature" => set_feature(setting, value, enable),
-        _ => unreachable!()
-    };
-
-    // Exit if error
-    match result {
+    // Run mode, exit if error
+    match execute(mode) {
         Ok(msg) => {
-            println!("{msg}");
+            if !msg.is_empty() {
+                println!("{msg}");
+            }
         },
         Err(error) => {
             eprintln!("{error}");
-            process::exit(1);
+            process::exit(error.code());
         }
     }
 }
 
 //---------------------------------------
-// DMI read helper function function
+// Execute function
 //---------------------------------------
-fn dmi_read(param: &str) -> Result<String, String> {
-    let file = format!("/sys/devices/virtual/dmi/id/{param}");
+fn execute(mode: Mode) -> Result<String, Error> {
+    // Check polkit authorization for requested operation
+    if let Some(authorization) = authorize::authorization(&mode) {
+        authorize::authorize(&authorization)?;
+    }
 
-    fs::read_to_string(file)
-        .map_err(|error| error.to_string())
-        .map(|value| value.trim().to_owned())
+    // Check mode
+    let output = match mode {
+        Mode::Get { feature, persisted } => get_features(feature, persisted)?,
+        Mode::Set { feature, value, backend, runtime, duration } => {
+            if let Some(seconds) = duration {
+                set_feature_timed(feature, value, seconds)?
+            } else if runtime {
+                set_feature_runtime(feature, value)?
+            } else {
+                set_feature(feature, value, backend)?
+            }
+        },
+        Mode::Revert { feature } => revert_feature(feature)?,
+        Mode::Status => status()?,
+        Mode::SystemInfo { reveal } => sysinfo::system_information(reveal)?,
+        Mode::Reset { feature } => reset_features(feature)?,
+        Mode::Reapply { feature, wait } => reapply_settings(feature, wait)?,
+        Mode::Enforce { feature, enabled } => watch::set_enforced(feature, enabled)?,
+        Mode::Watch => watch::watch()?,
+        Mode::GroupAccess { enable, disable } => {
+            match (enable, disable) {
+                (Some(group), _) => access::enable(&group)?,
+                (None, true) => access::disable()?,
+                (None, false) => access::status()?
+            }
+        },
+        Mode::Policy { apply } => {
+            if apply {
+                apply_policy()?
+            } else {
+                Policy::load()?.summary()
+            }
+        },
+        Mode::Repair { check } => {
+            if check {
+                repair::check_units()?
+            } else {
+                repair::repair_units()?
+            }
+        },
+        Mode::Diagnostics => diagnostics::diagnostics()?,
+        Mode::SelfCheck => selfcheck::self_check()?,
+        Mode::Session => session::session()?
+    };
+
+    Ok(output)
 }
 
 //---------------------------------------
-// System information function
-//---------------------------------------
-fn system_information() -> Result<String, String> {
-    let sys_vendor = dmi_read("sys_vendor")?;
-    let product_family = dmi_read("product_family")?;
-    let product_name = dmi_read("product_name")?;
-    let product_serial = dmi_read("product_serial")?;
-    let bios_vendor = dmi_read("bios_vendor")?;
-    let bios_version = dmi_read("bios_version")?;
-    let bios_date = dmi_read("bios_date")?;
-
-    let output = [
-        String::from("System Vendor"),
-        sys_vendor,
-        String::from("Product Family"),
-        product_family,
-        String::from("Product Name"),
-        product_name,
-        String::from("Serial Number"),
-        product_serial,
-        String::from("BIOS Vendor"),
-        bios_vendor,
-        String::from("BIOS Version"),
-        bios_version,
-        String::from("BIOS Date"),
-        bios_date,
-    ].join("\n");
+// Get features function
+//---------------------------------------
+fn get_features(feature: Option<&Feature>, persisted: bool) -> Result<String, String> {
+    // Persisted value defaults to feature default value
+    let read = |feature: &Feature| {
+        if persisted {
+            Ok(persistence::persisted(feature.id).unwrap_or_else(|| feature.default.to_owned()))
+        } else {
+            feature.read()
+        }
+    };
+
+    // Print value only for single feature
+    if let Some(feature) = feature {
+        return read(feature)
+    }
+
+    let output = FEATURES.iter()
+        .map(|feature| read(feature).map(|value| format!("{}={value}", feature.id)))
+        .collect::<Result<Vec<String>, String>>()?
+        .join("\n");
 
     Ok(output)
 }
@@ -88,107 +147,243 @@ fn system_information() -> Result<String, String> {
 //---------------------------------------
 // Set feature function
 //---------------------------------------
-fn set_feature(setting: &str, value: &str, enable: bool) -> Result<String, String> {
+fn set_feature(feature: &Feature, value: &str, backend: Option<&str>) -> Result<String, Error> {
+    let setting = feature.id;
+
+    // Check if settings file exists
+    feature.read()?;
+
+    // Check administrator policy
+    Policy::load()?.check(feature, value)?;
+
+    // Select persistence backend (default value is not persisted)
+    let backend = if value != feature.default {
+        let backend = match backend {
+            Some(name) => persistence::backend(name)
+                .ok_or_else(|| format!("ERROR: Unknown persistence backend {name}"))?,
+            None => persistence::detect(setting)?
+        };
+
+        backend.check(setting, value)?;
+
+        Some(backend)
+    } else {
+        None
+    };
+
+    // Write to settings file first, so that only applied values are persisted
+    feature.write(value)?;
+
+    // Update persistence
+    let message = if let Some(backend) = backend {
+        persistence::persist(backend.as_ref(), setting, value)?;
+
+        format!("Successfully changed {setting} setting (persisted with {})", backend.name())
+    } else {
+        persistence::clear(setting)?;
+
+        format!("Successfully changed {setting} setting")
+    };
+
+    // End temporary override
+    overrides::clear(setting)?;
+
+    // Install or remove resume hook
+    resume::sync_hook()?;
+
+    Ok(message)
+}
+
+//---------------------------------------
+// Set feature runtime function
+//---------------------------------------
+fn set_feature_runtime(feature: &Feature, value: &str) -> Result<String, Error> {
     // Check if settings file exists
-    let settings_file = format!("/sys/devices/platform/lg-laptop/{setting}");
+    feature.read()?;
 
-    fs::metadata(&settings_file)
-        .map_err(|_| format!("ERROR: {setting} setting file not found"))?;
+    // Check administrator policy
+    Policy::load()?.check(feature, value)?;
 
-    // Check if service unit file exists
-    let service_name = format!("lg_gram_{setting}_{value}.service");
+    // Write to settings file without touching persistence
+    feature.write(value)?;
 
-    if enable {
-        let unit_file = format!("/usr/lib/systemd/system/{service_name}");
+    // Record override only once applied, so that value is reapplied after resume
+    overrides::start_runtime(feature.id, value)?;
 
-        fs::metadata(&unit_file)
-            .map_err(|_| format!("ERROR: {service_name} unit file not found"))?;
+    resume::sync_hook()?;
+
+    Ok(format!("Successfully changed {} setting until reboot", feature.id))
+}
+
+//---------------------------------------
+// Set feature timed function
+//---------------------------------------
+fn set_feature_timed(feature: &Feature, value: &str, seconds: u64) -> Result<String, Error> {
+    // Check if settings file exists
+    let previous = feature.read()?;
+
+    // Check administrator policy
+    Policy::load()?.check(feature, value)?;
+
+    // Write to settings file without touching persistence
+    feature.write(value)?;
+
+    // Schedule revert to persisted value, restoring previous value if scheduling fails
+    if let Err(error) = overrides::start(feature, value, seconds) {
+        let _ = feature.write(&previous);
+
+        return Err(error.into())
     }
 
-    // Disable enabled services
-    for service in glob(&format!("/etc/systemd/system/**/lg_gram_{setting}_*.service"))
-        .expect("Failed to read glob pattern")
-        .flatten()
-        .map(|path| path.file_name().unwrap_or_default().to_string_lossy().to_string())
-        .collect::<Vec<String>>() {
-            let output = process::Command::new("systemctl")
-                .arg("disable")
-                .arg(service)
-                .output()
-                .map_err(|error| error.to_string())?;
-
-            if !output.status.success() {
-                return Err(String::from_utf8_lossy(&output.stderr).into())
-            }
-        }
+    // Reapply override or revert after resume
+    resume::sync_hook()?;
 
-    // Write to settings file
-    let content = format!("{value}\n");
+    Ok(format!("Successfully changed {} setting for {seconds} seconds", feature.id))
+}
 
-    fs::write(settings_file, content)
-        .map_err(|_| format!("ERROR: Error writing to {setting} setting file"))?;
+//---------------------------------------
+// Revert feature function
+//---------------------------------------
+fn revert_feature(feature: &Feature) -> Result<String, Error> {
+    overrides::clear(feature.id)?;
 
-    // Enable service if necessary
-    if enable {
-        let output = process::Command::new("systemctl")
-            .arg("enable")
-            .arg(service_name)
-            .output()
-            .map_err(|error| error.to_string())?;
+    resume::sync_hook()?;
 
-        if !output.status.success() {
-            return Err(String::from_utf8_lossy(&output.stderr).into())
-        }
+    let value = overrides::effective(feature)?
+        .unwrap_or_else(|| feature.default.to_owned());
 
+    if feature.read().is_ok() {
+        feature.write(&value)?;
     }
 
-    Ok(format!("Successfully changed {setting} setting"))
+    Ok(format!("Successfully reverted {} setting", feature.id))
 }
 
 //---------------------------------------
-// Validate args function
+// Status function
 //---------------------------------------
-fn validate_args(args: &[String]) -> Result<(&str, &str, &str, bool), ()> {
-    let Some(mode) = args.get(1) else {
-        return Err(());
-    };
+fn status() -> Result<String, String> {
+    let policy = Policy::load();
+
+    let output = FEATURES.iter()
+        .map(|feature| {
+            let current = feature.read()
+                .unwrap_or_else(|_| String::from("unavailable"));
+
+            let persisted = persistence::persisted_by(feature.id)
+                .map_or_else(|| String::from("not persisted"), |(value, backend)| {
+                    format!("persisted {value} with {backend}")
+                });
 
-    match mode.as_str() {
-        "--system-info" => { Ok((mode, "", "", false)) } 
-        "--feature" => {
-            let Some((setting, value)) = args.get(2).and_then(|arg| arg.split_once('=')) else {
-                return Err(());
+            let temporary = overrides::get(feature.id)
+                .map(|state| match state.remaining() {
+                    Some(remaining) => format!(", override {} for {remaining} seconds", state.value),
+                    None => format!(", override {} until reboot", state.value)
+                })
+                .unwrap_or_default();
+
+            let enforced = if watch::enforced().iter().any(|enforced| enforced.id == feature.id) {
+                ", enforced"
+            } else {
+                ""
             };
 
-            match (setting, value) {
-                ("battery_care_limit", value) if ["80", "100"].contains(&value) => {
-                    Ok((mode, setting, value, value != "100"))
-                },
-                ("fn_lock" | "usb_charge", value) if ["0", "1"].contains(&value) => {
-                    Ok((mode, setting, value, value != "0"))
-                },
-                ("fan_mode", value) if ["0", "1", "2"].contains(&value) => {
-                    Ok((mode, setting, value, value != "0"))
-                },
-                _ => {
-                    Err(())
-                }
-            }
-        },
-        _ => {
-            Err(())
+            let managed = if policy.as_ref().is_ok_and(|policy| policy.feature(feature.id).is_some()) {
+                ", managed by administrator"
+            } else {
+                ""
+            };
+
+            format!("{}: {current} ({persisted}{temporary}{enforced}{managed})", feature.id)
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    Ok(output)
+}
+
+//---------------------------------------
+// Reset features function
+//---------------------------------------
+fn reset_features(feature: Option<&Feature>) -> Result<String, Error> {
+    if let Some(feature) = feature {
+        return set_feature(feature, feature.default, None)
+    }
+
+    let policy = Policy::load()?;
+
+    let mut skipped = 0;
+
+    for feature in &FEATURES {
+        // Settings managed by administrator are kept
+        if policy.check(feature, feature.default).is_err() {
+            skipped += 1;
+            continue
+        }
+
+        persistence::clear(feature.id)?;
+        overrides::clear(feature.id)?;
+
+        if feature.read().is_ok() {
+            feature.write(feature.default)?;
+        }
+    }
+
+    resume::sync_hook()?;
+
+    if skipped > 0 {
+        return Ok(format!("Successfully reset all settings ({skipped} managed by your administrator kept)"))
+    }
+
+    Ok(String::from("Successfully reset all settings"))
+}
+
+//---------------------------------------
+// Apply policy function
+//---------------------------------------
+fn apply_policy() -> Result<String, Error> {
+    let policy = Policy::load()?;
+
+    let mut count = 0;
+
+    // Persist pinned values, so that they are applied at boot
+    for feature in &FEATURES {
+        if let Some(pin) = policy.pinned(feature.id) && feature.read().is_ok() {
+            set_feature(feature, pin, None)?;
+
+            count += 1;
         }
     }
+
+    Ok(format!("Successfully applied {count} pinned settings"))
 }
 
 //---------------------------------------
-// Eprint usage function
+// Reapply settings function
 //---------------------------------------
-fn eprint_usage(app_path: &str) {
-    let app_name = Path::new(app_path)
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy();
+fn reapply_settings(feature: Option<&Feature>, wait: bool) -> Result<String, Error> {
+    let features: Vec<&Feature> = feature.map_or_else(|| FEATURES.iter().collect(), |feature| vec![feature]);
+
+    // Driver creates setting files after device is added
+    if wait {
+        for _ in 0..REAPPLY_WAIT_ATTEMPTS {
+            if features.iter().all(|feature| feature.read().is_ok()) {
+                break
+            }
+
+            thread::sleep(REAPPLY_WAIT_INTERVAL);
+        }
+    }
+
+    let mut count = 0;
+
+    for feature in features {
+        if let Some(value) = overrides::effective(feature)? && feature.read().is_ok() {
+            feature.write(&value)?;
+
+            count += 1;
+        }
+    }
 
-    eprintln!("ERROR: USAGE: {app_name} mode setting=value");
+    Ok(format!("Successfully reapplied {count} settings"))
 }
@@ -0,0 +1,273 @@
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+
+use crate::WRITER;
+use crate::features::Feature;
+use crate::persistence::{self, run_command, systemd_running};
+use crate::policy::Policy;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+pub const OVERRIDES_PATH: &str = "/run/lg-gram-settings/overrides";
+
+// Longest temporary override (7 days)
+const MAX_DURATION: u64 = 7 * 24 * 3600;
+
+//------------------------------------------------------------------------------
+// STRUCT: Override
+//------------------------------------------------------------------------------
+// Live value differing from persisted value: timed (with expiry) or until reboot
+pub struct Override {
+    pub value: String,
+    pub expiry: Option<u64>,
+    pub pid: Option<u32>,
+}
+
+impl Override {
+    //---------------------------------------
+    // Remaining function
+    //---------------------------------------
+    pub fn remaining(&self) -> Option<u64> {
+        self.expiry.map(|expiry| expiry.saturating_sub(now()))
+    }
+
+    //---------------------------------------
+    // Is expired function
+    //---------------------------------------
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Some(0)
+    }
+}
+
+//---------------------------------------
+// Now helper function
+//---------------------------------------
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+//---------------------------------------
+// State file helper function
+//---------------------------------------
+fn state_file(setting: &str) -> String {
+    format!("{OVERRIDES_PATH}/{setting}")
+}
+
+//---------------------------------------
+// Timer unit helper function
+//---------------------------------------
+fn timer_unit(setting: &str) -> String {
+    format!("lg_gram_override_{setting}")
+}
+
+//---------------------------------------
+// Parse duration function
+//---------------------------------------
+pub fn parse_duration(duration: &str) -> Result<u64, String> {
+    let (number, multiplier) = if let Some(number) = duration.strip_suffix('h') {
+        (number, 3600)
+    } else if let Some(number) = duration.strip_suffix('m') {
+        (number, 60)
+    } else if let Some(number) = duration.strip_suffix('s') {
+        (number, 1)
+    } else {
+        (duration, 60)
+    };
+
+    number.parse::<u64>().ok()
+        .filter(|number| *number > 0)
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|seconds| *seconds <= MAX_DURATION)
+        .ok_or_else(|| String::from("expected a positive number followed by s, m or h (e.g. 30m, 2h), up to 168h"))
+}
+
+//---------------------------------------
+// Get override function
+//---------------------------------------
+pub fn get(setting: &str) -> Option<Override> {
+    let content = fs::read_to_string(state_file(setting)).ok()?;
+
+    let field = |key: &str| {
+        content.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(str::trim)
+    };
+
+    Some(Override {
+        value: field("value")?.to_owned(),
+        expiry: field("expiry").and_then(|expiry| expiry.parse().ok()),
+        pid: field("pid").and_then(|pid| pid.parse().ok()),
+    })
+}
+
+//---------------------------------------
+// Effective value function
+//---------------------------------------
+pub fn effective(feature: &Feature) -> Result<Option<String>, String> {
+    // Administrator pin takes precedence
+    if let Some(pin) = Policy::load()?.pinned(feature.id) {
+        return Ok(Some(pin.to_owned()))
+    }
+
+    // Active override takes precedence, expired override is reverted
+    match get(feature.id) {
+        Some(state) if !state.is_expired() => Ok(Some(state.value)),
+        Some(_) => {
+            clear(feature.id)?;
+
+            Ok(Some(persistence::persisted(feature.id).unwrap_or_else(|| feature.default.to_owned())))
+        },
+        None => Ok(persistence::persisted(feature.id))
+    }
+}
+
+//---------------------------------------
+// Save state helper function
+//---------------------------------------
+fn save_state(setting: &str, content: &str) -> Result<(), String> {
+    fs::create_dir_all(OVERRIDES_PATH)
+        .and_then(|()| fs::write(state_file(setting), content))
+        .map_err(|_| format!("ERROR: Error writing to {}", state_file(setting)))
+}
+
+//---------------------------------------
+// Start runtime override function
+//---------------------------------------
+pub fn start_runtime(setting: &str, value: &str) -> Result<(), String> {
+    // Replace existing override
+    clear(setting)?;
+
+    // State is kept in /run, so it does not survive a reboot
+    save_state(setting, &format!("value={value}\n"))
+}
+
+//---------------------------------------
+// Start override function
+//---------------------------------------
+pub fn start(feature: &Feature, value: &str, seconds: u64) -> Result<(), String> {
+    let setting = feature.id;
+
+    // Replace existing override
+    clear(setting)?;
+
+    // Schedule revert with transient systemd timer, or detached process otherwise
+    let pid = if systemd_running() {
+        let unit = timer_unit(setting);
+
+        let _ = run_command("systemctl", &["reset-failed", &format!("{unit}.timer"), &format!("{unit}.service")]);
+
+        run_command("systemd-run", &[
+            &format!("--unit={unit}"),
+            &format!("--on-active={seconds}s"),
+            "--timer-property=AccuracySec=1s",
+            &format!("--description=Revert LG Gram {} override", feature.name),
+            WRITER, "revert", setting
+        ])
+        .map_err(|error| format!("ERROR: Failed to schedule revert of {setting} setting: {}", error.trim()))?;
+
+        None
+    } else {
+        let child = revert_command(setting, seconds)
+            .spawn()
+            .map_err(|error| format!("ERROR: Failed to schedule revert of {setting} setting: {error}"))?;
+
+        Some(child.id())
+    };
+
+    // Save override state
+    let mut content = format!("value={value}\nexpiry={}\n", now().saturating_add(seconds));
+
+    if let Some(pid) = pid {
+        content.push_str(&format!("pid={pid}\n"));
+    }
+
+    save_state(setting, &content)
+}
+
+//---------------------------------------
+// Revert command helper function
+//---------------------------------------
+// Detached revert for systems without systemd
+fn revert_command(setting: &str, seconds: u64) -> process::Command {
+    let mut command = process::Command::new("sh");
+
+    // Revert already runs as root, its parent is no longer the pkexec caller, so it must not check authorization
+    command.arg("-c")
+        .arg(format!("sleep {seconds} && exec {WRITER} revert {setting}"))
+        .env_remove("PKEXEC_UID")
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .process_group(0);
+
+    command
+}
+
+//---------------------------------------
+// Clear override function
+//---------------------------------------
+pub fn clear(setting: &str) -> Result<(), String> {
+    let Some(state) = get(setting) else {
+        return Ok(())
+    };
+
+    // Cancel scheduled revert (the revert process itself must not be killed)
+    match state.pid {
+        Some(pid) if pid != process::id() => {
+            let _ = signal::killpg(Pid::from_raw(pid.cast_signed()), Signal::SIGTERM);
+        },
+        Some(_) => {},
+        None if state.expiry.is_some() => {
+            let _ = run_command("systemctl", &["stop", &format!("{}.timer", timer_unit(setting))]);
+        },
+        None => {}
+    }
+
+    fs::remove_file(state_file(setting))
+        .map_err(|_| format!("ERROR: Error removing {}", state_file(setting)))
+}
+
+//------------------------------------------------------------------------------
+// TESTS
+//------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    #[test]
+    fn revert_command_runs_writer_after_delay() {
+        let command = revert_command("fan_mode", 90);
+
+        let args: Vec<&OsStr> = command.get_args().collect();
+
+        assert_eq!(command.get_program(), "sh");
+        assert_eq!(args, [OsStr::new("-c"), OsStr::new(&format!("sleep 90 && exec {WRITER} revert fan_mode"))]);
+    }
+
+    #[test]
+    fn revert_command_skips_pkexec_authorization() {
+        let command = revert_command("fan_mode", 90);
+
+        let pkexec_uid = command.get_envs()
+            .find(|(key, _)| *key == "PKEXEC_UID");
+
+        assert_eq!(pkexec_uid, Some((OsStr::new("PKEXEC_UID"), None)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_overlong_durations() {
+        assert_eq!(parse_duration("168h"), Ok(MAX_DURATION));
+        assert!(parse_duration("169h").is_err());
+        assert!(parse_duration("18446744073709551615h").is_err());
+    }
+}
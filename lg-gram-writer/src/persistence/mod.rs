@@ -0,0 +1,223 @@
+mod systemd;
+mod openrc;
+mod runit;
+mod udev;
+mod tmpfiles;
+
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use crate::SETTINGS_PATH;
+
+use systemd::Systemd;
+use openrc::OpenRC;
+use runit::Runit;
+use udev::Udev;
+use tmpfiles::Tmpfiles;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+pub const UNIT_PATH: &str = "/usr/lib/systemd/system";
+pub const ENABLED_PATH: &str = "/etc/systemd/system";
+
+const MANAGED_HEADER: &str = "# Managed by lg-gram-writer: do not edit";
+
+//------------------------------------------------------------------------------
+// TRAIT: Backend
+//------------------------------------------------------------------------------
+pub trait Backend {
+    // Backend name
+    fn name(&self) -> &'static str;
+
+    // Check if backend is the default for this system
+    fn is_default(&self) -> bool;
+
+    // Persisted value for setting (if any)
+    fn persisted(&self, setting: &str) -> Option<String>;
+
+    // Check if setting value can be persisted (before settings file is written)
+    fn check(&self, _setting: &str, _value: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Persist setting value
+    fn persist(&self, setting: &str, value: &str) -> Result<(), String>;
+
+    // Remove setting from persistence
+    fn clear(&self, setting: &str) -> Result<(), String>;
+}
+
+//---------------------------------------
+// Backend names
+//---------------------------------------
+pub const BACKEND_NAMES: [&str; 5] = ["systemd", "openrc", "runit", "udev", "tmpfiles"];
+
+//---------------------------------------
+// All backends function
+//---------------------------------------
+pub fn backends() -> Vec<Box<dyn Backend>> {
+    vec![Box::new(Systemd), Box::new(OpenRC), Box::new(Runit), Box::new(Udev), Box::new(Tmpfiles)]
+}
+
+//---------------------------------------
+// Backend by name function
+//---------------------------------------
+pub fn backend(name: &str) -> Option<Box<dyn Backend>> {
+    backends().into_iter()
+        .find(|backend| backend.name() == name)
+}
+
+//---------------------------------------
+// Detect backend function
+//---------------------------------------
+pub fn detect(setting: &str) -> Result<Box<dyn Backend>, String> {
+    let mut backends = backends();
+
+    // Prefer backend that already persists setting
+    if let Some(index) = backends.iter().position(|backend| backend.persisted(setting).is_some()) {
+        return Ok(backends.swap_remove(index))
+    }
+
+    // Otherwise use backend for running init system
+    backends.into_iter()
+        .find(|backend| backend.is_default())
+        .ok_or_else(|| String::from("ERROR: No supported init system detected"))
+}
+
+//---------------------------------------
+// Persist function
+//---------------------------------------
+pub fn persist(backend: &dyn Backend, setting: &str, value: &str) -> Result<(), String> {
+    // Remove setting from other backends
+    for other in backends().iter().filter(|other| other.name() != backend.name()) {
+        other.clear(setting)?;
+    }
+
+    backend.persist(setting, value)
+}
+
+//---------------------------------------
+// Persisted value function
+//---------------------------------------
+pub fn persisted(setting: &str) -> Option<String> {
+    persisted_by(setting)
+        .map(|(value, _)| value)
+}
+
+//---------------------------------------
+// Persisted value and backend function
+//---------------------------------------
+pub fn persisted_by(setting: &str) -> Option<(String, &'static str)> {
+    backends().iter()
+        .find_map(|backend| backend.persisted(setting).map(|value| (value, backend.name())))
+}
+
+//---------------------------------------
+// Clear function
+//---------------------------------------
+pub fn clear(setting: &str) -> Result<(), String> {
+    for backend in backends() {
+        backend.clear(setting)?;
+    }
+
+    Ok(())
+}
+
+//---------------------------------------
+// Shell command helper function
+//---------------------------------------
+fn shell_command(setting: &str, value: &str) -> String {
+    format!("echo \"{value}\" > {SETTINGS_PATH}/{setting}")
+}
+
+//---------------------------------------
+// Shell command value helper function
+//---------------------------------------
+fn shell_command_value(line: &str) -> Option<String> {
+    line.trim()
+        .strip_prefix("echo \"")
+        .and_then(|line| line.split_once('"'))
+        .map(|(value, _)| value.to_owned())
+}
+
+//---------------------------------------
+// Managed file value helper function
+//---------------------------------------
+// Managed files hold one line per persisted setting, parsed by line_value
+fn managed_file_value(path: &str, setting: &str, line_value: fn(&str, &str) -> Option<String>) -> Option<String> {
+    fs::read_to_string(path).ok()?
+        .lines()
+        .find_map(|line| line_value(line, setting))
+}
+
+//---------------------------------------
+// Update managed file helper function
+//---------------------------------------
+// Replace or remove line of setting, returns false if file was left unchanged
+fn update_managed_file(
+    path: &str,
+    setting: &str,
+    value: Option<&str>,
+    line: fn(&str, &str) -> String,
+    line_value: fn(&str, &str) -> Option<String>
+) -> Result<bool, String> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+
+    // Nothing to do if setting is not persisted
+    if value.is_none() && !content.lines().any(|other| line_value(other, setting).is_some()) {
+        return Ok(false)
+    }
+
+    let mut lines: Vec<String> = content.lines()
+        .filter(|other| !other.starts_with('#') && !other.is_empty())
+        .filter(|other| line_value(other, setting).is_none())
+        .map(ToOwned::to_owned)
+        .collect();
+
+    if let Some(value) = value {
+        lines.push(line(setting, value));
+    }
+
+    // Remove file if empty
+    if lines.is_empty() {
+        fs::remove_file(path)
+            .map_err(|_| format!("ERROR: Error removing {path}"))?;
+    } else {
+        fs::write(path, format!("{MANAGED_HEADER}\n{}\n", lines.join("\n")))
+            .map_err(|_| format!("ERROR: Error writing to {path}"))?;
+    }
+
+    Ok(true)
+}
+
+//---------------------------------------
+// Run command helper function
+//---------------------------------------
+pub fn run_command(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|error| error.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into())
+    }
+
+    Ok(())
+}
+
+//---------------------------------------
+// Path exists helper function
+//---------------------------------------
+pub fn exists(path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+//---------------------------------------
+// Systemd running helper function
+//---------------------------------------
+pub fn systemd_running() -> bool {
+    exists("/run/systemd/system")
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use super::{Backend, shell_command, shell_command_value, run_command, exists};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const LOCAL_PATH: &str = "/etc/local.d";
+const LOCAL_SERVICE: &str = "/etc/runlevels/default/local";
+
+//------------------------------------------------------------------------------
+// STRUCT: OpenRC
+//------------------------------------------------------------------------------
+pub struct OpenRC;
+
+impl OpenRC {
+    //---------------------------------------
+    // Script file helper function
+    //---------------------------------------
+    fn script_file(setting: &str) -> String {
+        format!("{LOCAL_PATH}/lg_gram_{setting}.start")
+    }
+}
+
+impl Backend for OpenRC {
+    fn name(&self) -> &'static str {
+        "openrc"
+    }
+
+    fn is_default(&self) -> bool {
+        exists("/run/openrc")
+    }
+
+    fn persisted(&self, setting: &str) -> Option<String> {
+        fs::read_to_string(Self::script_file(setting)).ok()?
+            .lines()
+            .find_map(shell_command_value)
+    }
+
+    fn persist(&self, setting: &str, value: &str) -> Result<(), String> {
+        // Write local.d script
+        let script_file = Self::script_file(setting);
+
+        let content = format!("#!/bin/sh\n# Managed by lg-gram-writer\n{}\n", shell_command(setting, value));
+
+        fs::create_dir_all(LOCAL_PATH)
+            .and_then(|()| fs::write(&script_file, content))
+            .and_then(|()| fs::set_permissions(&script_file, fs::Permissions::from_mode(0o755)))
+            .map_err(|_| format!("ERROR: Error writing to {script_file}"))?;
+
+        // Make sure local service is enabled
+        if !exists(LOCAL_SERVICE) {
+            run_command("rc-update", &["add", "local", "default"])?;
+        }
+
+        Ok(())
+    }
+
+    fn clear(&self, setting: &str) -> Result<(), String> {
+        let script_file = Self::script_file(setting);
+
+        if exists(&script_file) {
+            fs::remove_file(&script_file)
+                .map_err(|_| format!("ERROR: Error removing {script_file}"))?;
+        }
+
+        Ok(())
+    }
+}
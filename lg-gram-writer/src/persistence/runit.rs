@@ -0,0 +1,88 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use super::{Backend, shell_command, shell_command_value, exists};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const RC_LOCAL_FILES: [&str; 2] = ["/etc/rc.local", "/etc/rc/rc.local"];
+
+//------------------------------------------------------------------------------
+// STRUCT: Runit
+//------------------------------------------------------------------------------
+pub struct Runit;
+
+impl Runit {
+    //---------------------------------------
+    // Rc.local file helper function
+    //---------------------------------------
+    fn rc_local_file() -> &'static str {
+        RC_LOCAL_FILES.into_iter()
+            .find(|file| exists(file))
+            .unwrap_or(RC_LOCAL_FILES[0])
+    }
+
+    //---------------------------------------
+    // Line marker helper function
+    //---------------------------------------
+    fn marker(setting: &str) -> String {
+        format!("# lg_gram_{setting}")
+    }
+
+    //---------------------------------------
+    // Update rc.local helper function
+    //---------------------------------------
+    fn update(setting: &str, value: Option<&str>) -> Result<(), String> {
+        let file = Self::rc_local_file();
+        let marker = Self::marker(setting);
+
+        let content = fs::read_to_string(file)
+            .unwrap_or_else(|_| String::from("#!/bin/sh\n"));
+
+        // Nothing to do if setting is not persisted
+        if value.is_none() && !content.lines().any(|line| line.ends_with(&marker)) {
+            return Ok(())
+        }
+
+        let mut lines: Vec<String> = content.lines()
+            .filter(|line| !line.ends_with(&marker))
+            .map(ToOwned::to_owned)
+            .collect();
+
+        if let Some(value) = value {
+            lines.push(format!("{} {marker}", shell_command(setting, value)));
+        }
+
+        fs::write(file, lines.join("\n") + "\n")
+            .and_then(|()| fs::set_permissions(file, fs::Permissions::from_mode(0o755)))
+            .map_err(|_| format!("ERROR: Error writing to {file}"))
+    }
+}
+
+impl Backend for Runit {
+    fn name(&self) -> &'static str {
+        "runit"
+    }
+
+    fn is_default(&self) -> bool {
+        exists("/run/runit")
+    }
+
+    fn persisted(&self, setting: &str) -> Option<String> {
+        let marker = Self::marker(setting);
+
+        fs::read_to_string(Self::rc_local_file()).ok()?
+            .lines()
+            .filter(|line| line.ends_with(&marker))
+            .find_map(shell_command_value)
+    }
+
+    fn persist(&self, setting: &str, value: &str) -> Result<(), String> {
+        Self::update(setting, Some(value))
+    }
+
+    fn clear(&self, setting: &str) -> Result<(), String> {
+        Self::update(setting, None)
+    }
+}
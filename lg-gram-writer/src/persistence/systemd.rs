@@ -0,0 +1,74 @@
+use std::fs;
+
+use glob::glob;
+
+use super::{Backend, UNIT_PATH, ENABLED_PATH, run_command, systemd_running};
+
+//------------------------------------------------------------------------------
+// STRUCT: Systemd
+//------------------------------------------------------------------------------
+pub struct Systemd;
+
+impl Systemd {
+    //---------------------------------------
+    // Enabled services helper function
+    //---------------------------------------
+    fn enabled_services(setting: &str) -> Vec<String> {
+        // Ignore masked, dangling and drop-in entries (see repair mode)
+        glob(&format!("{ENABLED_PATH}/*.wants/lg_gram_{setting}_*.service"))
+            .expect("Failed to read glob pattern")
+            .flatten()
+            .filter(|path| path.exists())
+            .map(|path| path.file_name().unwrap_or_default().to_string_lossy().to_string())
+            .collect()
+    }
+}
+
+impl Backend for Systemd {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn is_default(&self) -> bool {
+        systemd_running()
+    }
+
+    fn persisted(&self, setting: &str) -> Option<String> {
+        Self::enabled_services(setting).first()
+            .and_then(|service| {
+                service.strip_prefix(&format!("lg_gram_{setting}_"))
+                    .and_then(|service| service.strip_suffix(".service"))
+                    .map(ToOwned::to_owned)
+            })
+    }
+
+    fn check(&self, setting: &str, value: &str) -> Result<(), String> {
+        // Check if service unit file exists
+        let service_name = format!("lg_gram_{setting}_{value}.service");
+
+        fs::metadata(format!("{UNIT_PATH}/{service_name}"))
+            .map_err(|_| format!("ERROR: {service_name} unit file not found"))?;
+
+        Ok(())
+    }
+
+    fn persist(&self, setting: &str, value: &str) -> Result<(), String> {
+        self.check(setting, value)?;
+
+        let service_name = format!("lg_gram_{setting}_{value}.service");
+
+        // Disable enabled services
+        self.clear(setting)?;
+
+        // Enable service
+        run_command("systemctl", &["enable", &service_name])
+    }
+
+    fn clear(&self, setting: &str) -> Result<(), String> {
+        for service in Self::enabled_services(setting) {
+            run_command("systemctl", &["disable", &service])?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use crate::SETTINGS_PATH;
+
+use super::{Backend, managed_file_value, update_managed_file};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const TMPFILES_FILE: &str = "/etc/tmpfiles.d/lg-gram-settings.conf";
+
+//------------------------------------------------------------------------------
+// STRUCT: Tmpfiles
+//------------------------------------------------------------------------------
+pub struct Tmpfiles;
+
+impl Tmpfiles {
+    //---------------------------------------
+    // Line helper function
+    //---------------------------------------
+    fn line(setting: &str, value: &str) -> String {
+        format!("w {SETTINGS_PATH}/{setting} - - - - {value}")
+    }
+
+    //---------------------------------------
+    // Line value helper function
+    //---------------------------------------
+    fn line_value(line: &str, setting: &str) -> Option<String> {
+        line.strip_prefix(&format!("w {SETTINGS_PATH}/{setting} "))
+            .and_then(|line| line.split_whitespace().nth(4))
+            .map(ToOwned::to_owned)
+    }
+
+    //---------------------------------------
+    // Update tmpfiles helper function
+    //---------------------------------------
+    fn update(setting: &str, value: Option<&str>) -> Result<(), String> {
+        update_managed_file(TMPFILES_FILE, setting, value, Self::line, Self::line_value)
+            .map(|_| ())
+    }
+}
+
+impl Backend for Tmpfiles {
+    fn name(&self) -> &'static str {
+        "tmpfiles"
+    }
+
+    fn is_default(&self) -> bool {
+        // Only used when explicitly selected
+        false
+    }
+
+    fn persisted(&self, setting: &str) -> Option<String> {
+        managed_file_value(TMPFILES_FILE, setting, Self::line_value)
+    }
+
+    fn persist(&self, setting: &str, value: &str) -> Result<(), String> {
+        Self::update(setting, Some(value))
+    }
+
+    fn clear(&self, setting: &str) -> Result<(), String> {
+        Self::update(setting, None)
+    }
+}
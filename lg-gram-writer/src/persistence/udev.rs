@@ -0,0 +1,69 @@
+use crate::WRITER;
+
+use super::{Backend, managed_file_value, update_managed_file, run_command, exists};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const RULES_FILE: &str = "/etc/udev/rules.d/90-lg-gram-settings.rules";
+
+//------------------------------------------------------------------------------
+// STRUCT: Udev
+//------------------------------------------------------------------------------
+pub struct Udev;
+
+impl Udev {
+    //---------------------------------------
+    // Rule helper function
+    //---------------------------------------
+    // Setting files are created after the add event, so value is applied by writer instead of ATTR{}
+    fn rule(setting: &str, value: &str) -> String {
+        format!("ACTION==\"add\", SUBSYSTEM==\"platform\", KERNEL==\"lg-laptop\", ENV{{LG_GRAM_{setting}}}=\"{value}\", RUN+=\"{WRITER} reapply --wait {setting}\"")
+    }
+
+    //---------------------------------------
+    // Rule value helper function
+    //---------------------------------------
+    fn rule_value(line: &str, setting: &str) -> Option<String> {
+        line.split_once(&format!("ENV{{LG_GRAM_{setting}}}=\""))
+            .and_then(|(_, value)| value.split_once('"'))
+            .map(|(value, _)| value.to_owned())
+    }
+
+    //---------------------------------------
+    // Update rules helper function
+    //---------------------------------------
+    fn update(setting: &str, value: Option<&str>) -> Result<(), String> {
+        let changed = update_managed_file(RULES_FILE, setting, value, Self::rule, Self::rule_value)?;
+
+        // Reload udev rules
+        if changed && exists("/run/udev/control") {
+            run_command("udevadm", &["control", "--reload"])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for Udev {
+    fn name(&self) -> &'static str {
+        "udev"
+    }
+
+    fn is_default(&self) -> bool {
+        // Only used when explicitly selected
+        false
+    }
+
+    fn persisted(&self, setting: &str) -> Option<String> {
+        managed_file_value(RULES_FILE, setting, Self::rule_value)
+    }
+
+    fn persist(&self, setting: &str, value: &str) -> Result<(), String> {
+        Self::update(setting, Some(value))
+    }
+
+    fn clear(&self, setting: &str) -> Result<(), String> {
+        Self::update(setting, None)
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::MetadataExt;
+
+use serde::Deserialize;
+
+use crate::error::{Category, Error};
+use crate::features::{self, Feature, FEATURES};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+pub const POLICY_FILE: &str = "/etc/lg-gram-settings/policy.toml";
+
+//------------------------------------------------------------------------------
+// STRUCT: FeaturePolicy
+//------------------------------------------------------------------------------
+// Example: [fan_mode] allowed = ["0", "1"]
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FeaturePolicy {
+    pub pin: Option<String>,
+    pub allowed: Option<Vec<String>>,
+    pub read_only: bool,
+}
+
+impl FeaturePolicy {
+    //---------------------------------------
+    // Check value function
+    //---------------------------------------
+    fn check(&self, feature: &Feature, value: &str) -> Result<(), String> {
+        let label = feature.label(value);
+
+        if self.read_only {
+            return Err(format!("ERROR: {} is managed by your administrator (read-only)", feature.name))
+        }
+
+        if let Some(pin) = self.pin.as_deref().filter(|pin| *pin != value) {
+            return Err(format!("ERROR: {} is managed by your administrator (pinned to {})", feature.name, feature.label(pin)))
+        }
+
+        if self.allowed.as_ref().is_some_and(|allowed| !allowed.iter().any(|allowed| allowed == value)) {
+            return Err(format!("ERROR: {} '{label}' is not allowed by your administrator", feature.name))
+        }
+
+        Ok(())
+    }
+}
+
+//------------------------------------------------------------------------------
+// STRUCT: Policy
+//------------------------------------------------------------------------------
+#[derive(Default)]
+pub struct Policy {
+    features: HashMap<String, FeaturePolicy>,
+}
+
+impl Policy {
+    //---------------------------------------
+    // Load function
+    //---------------------------------------
+    pub fn load() -> Result<Self, String> {
+        let metadata = match fs::metadata(POLICY_FILE) {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(format!("ERROR: Error reading {POLICY_FILE}: {error}"))
+        };
+
+        // Policy must only be writable by root
+        if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+            return Err(format!("ERROR: {POLICY_FILE} must be owned by root and not writable by group or others"))
+        }
+
+        let content = fs::read_to_string(POLICY_FILE)
+            .map_err(|error| format!("ERROR: Error reading {POLICY_FILE}: {error}"))?;
+
+        let features: HashMap<String, FeaturePolicy> = toml::from_str(&content)
+            .map_err(|error| format!("ERROR: Invalid {POLICY_FILE}: {}", error.message()))?;
+
+        // Validate feature ids and values
+        for (id, policy) in &features {
+            let feature = features::feature(id)
+                .ok_or_else(|| format!("ERROR: Invalid {POLICY_FILE}: unknown feature {id}"))?;
+
+            let invalid = policy.pin.iter()
+                .chain(policy.allowed.iter().flatten())
+                .find(|value| feature.value(value).is_none());
+
+            if let Some(value) = invalid {
+                return Err(format!("ERROR: Invalid {POLICY_FILE}: unknown {id} value {value}"))
+            }
+        }
+
+        Ok(Self { features })
+    }
+
+    //---------------------------------------
+    // Feature policy function
+    //---------------------------------------
+    pub fn feature(&self, id: &str) -> Option<&FeaturePolicy> {
+        self.features.get(id)
+    }
+
+    //---------------------------------------
+    // Pinned value function
+    //---------------------------------------
+    pub fn pinned(&self, id: &str) -> Option<&str> {
+        self.feature(id)
+            .and_then(|policy| policy.pin.as_deref())
+    }
+
+    //---------------------------------------
+    // Check value function
+    //---------------------------------------
+    pub fn check(&self, feature: &Feature, value: &str) -> Result<(), Error> {
+        self.feature(feature.id)
+            .map_or(Ok(()), |policy| policy.check(feature, value))
+            .map_err(|message| Error::new(Category::Policy, message))
+    }
+
+    //---------------------------------------
+    // Summary function
+    //---------------------------------------
+    pub fn summary(&self) -> String {
+        FEATURES.iter()
+            .filter_map(|feature| {
+                let policy = self.feature(feature.id)?;
+
+                let rule = if policy.read_only {
+                    String::from("read-only")
+                } else if let Some(pin) = &policy.pin {
+                    format!("pinned:{pin}")
+                } else {
+                    format!("allowed:{}", policy.allowed.as_ref()?.join(","))
+                };
+
+                Some(format!("{}={rule}", feature.id))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+
+use crate::features::FEATURES;
+use crate::persistence::{ENABLED_PATH, run_command, systemd_running};
+use crate::resume::{self, RESUME_SERVICE};
+use crate::watch::WATCH_SERVICE;
+
+//------------------------------------------------------------------------------
+// ENUM: Fix
+//------------------------------------------------------------------------------
+enum Fix {
+    Disable(String),
+    Unmask(String),
+    Remove(PathBuf),
+}
+
+impl Fix {
+    //---------------------------------------
+    // Description function
+    //---------------------------------------
+    fn description(&self) -> String {
+        match self {
+            Self::Disable(unit) => format!("Run: systemctl disable {unit}"),
+            Self::Unmask(unit) => format!("Run: systemctl unmask {unit}"),
+            Self::Remove(path) => format!("Remove {}", path.display()),
+        }
+    }
+
+    //---------------------------------------
+    // Apply function
+    //---------------------------------------
+    fn apply(&self) -> Result<(), String> {
+        match self {
+            Self::Disable(unit) => run_command("systemctl", &["disable", unit]),
+            Self::Unmask(unit) => run_command("systemctl", &["unmask", unit]),
+            Self::Remove(path) => {
+                if path.is_dir() && !path.is_symlink() {
+                    fs::remove_dir_all(path)
+                } else {
+                    fs::remove_file(path)
+                }
+                .map_err(|error| format!("ERROR: Error removing {}: {error}", path.display()))
+            }
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// STRUCT: Problem
+//------------------------------------------------------------------------------
+struct Problem {
+    kind: &'static str,
+    description: String,
+    fix: Fix,
+}
+
+//---------------------------------------
+// File name helper function
+//---------------------------------------
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+//---------------------------------------
+// Parse unit helper function
+//---------------------------------------
+fn parse_unit(unit: &str) -> Option<(&'static str, &'static str)> {
+    let name = unit.strip_prefix("lg_gram_")?.strip_suffix(".service")?;
+
+    FEATURES.iter().find_map(|feature| {
+        let value = name.strip_prefix(feature.id)?.strip_prefix('_')?;
+
+        feature.value(value)
+            .filter(|value| *value != feature.default)
+            .map(|value| (feature.id, value))
+    })
+}
+
+//---------------------------------------
+// Problems function
+//---------------------------------------
+fn problems() -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut enabled: HashMap<&str, Vec<(&str, String)>> = HashMap::new();
+
+    for path in glob(&format!("{ENABLED_PATH}/**/lg_gram_*")).expect("Failed to read glob pattern").flatten() {
+        let unit = file_name(&path);
+
+        let parent = path.parent().map(file_name).unwrap_or_default();
+
+        // Drop-in directory for an lg_gram unit, or unit inside a drop-in directory
+        if unit.ends_with(".d") || parent.ends_with(".d") {
+            problems.push(Problem {
+                kind: "Drop-in",
+                description: format!("{} modifies lg_gram units", path.display()),
+                fix: Fix::Remove(path),
+            });
+
+            continue
+        }
+
+        if path.is_symlink() {
+            let target = fs::read_link(&path).unwrap_or_default();
+
+            if target == Path::new("/dev/null") {
+                problems.push(Problem {
+                    kind: "Masked",
+                    description: format!("{unit} is masked"),
+                    fix: Fix::Unmask(unit),
+                });
+
+                continue
+            }
+
+            if !path.exists() {
+                problems.push(Problem {
+                    kind: "Dangling",
+                    description: format!("{} points to missing {}", path.display(), target.display()),
+                    fix: Fix::Remove(path),
+                });
+
+                continue
+            }
+        } else if path.is_file() {
+            problems.push(Problem {
+                kind: "Shadowed",
+                description: format!("Local copy {} overrides the installed unit", path.display()),
+                fix: Fix::Remove(path),
+            });
+
+            continue
+        }
+
+        if unit == RESUME_SERVICE || unit == WATCH_SERVICE {
+            continue
+        }
+
+        match parse_unit(&unit) {
+            Some((setting, value)) => {
+                let units = enabled.entry(setting).or_default();
+
+                if !units.iter().any(|(other, _)| *other == value) {
+                    units.push((value, unit));
+                }
+            },
+            None => {
+                problems.push(Problem {
+                    kind: "Orphaned",
+                    description: format!("{unit} does not match any setting value"),
+                    fix: Fix::Remove(path),
+                });
+            }
+        }
+    }
+
+    // Several values enabled for the same setting
+    for feature in &FEATURES {
+        let Some(units) = enabled.get(feature.id).filter(|units| units.len() > 1) else {
+            continue
+        };
+
+        // Keep unit matching live value, or first unit otherwise
+        let live = feature.read().unwrap_or_default();
+
+        let keep = units.iter()
+            .position(|(value, _)| *value == live)
+            .unwrap_or_default();
+
+        for (i, (_, unit)) in units.iter().enumerate() {
+            if i != keep {
+                problems.push(Problem {
+                    kind: "Conflict",
+                    description: format!("{unit} conflicts with {} (boot order is arbitrary)", units[keep].1),
+                    fix: Fix::Disable(unit.clone()),
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+//---------------------------------------
+// Check units function
+//---------------------------------------
+pub fn check_units() -> Result<String, String> {
+    let problems = problems();
+
+    if problems.is_empty() {
+        return Ok(String::from("No unit problems found"))
+    }
+
+    // Print report, fail if any problem was found
+    let report = problems.iter()
+        .map(|problem| format!("{}: {}\n  FIX {}", problem.kind, problem.description, problem.fix.description()))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    println!("{report}");
+
+    Err(format!("ERROR: {} unit problems found", problems.len()))
+}
+
+//---------------------------------------
+// Repair units function
+//---------------------------------------
+pub fn repair_units() -> Result<String, String> {
+    let problems = problems();
+
+    if problems.is_empty() {
+        return Ok(String::from("No unit problems found"))
+    }
+
+    let mut report = vec![];
+
+    for problem in &problems {
+        problem.fix.apply()?;
+
+        report.push(format!("Fixed {}: {}", problem.kind, problem.description));
+    }
+
+    if systemd_running() {
+        run_command("systemctl", &["daemon-reload"])?;
+    }
+
+    resume::sync_hook()?;
+
+    report.push(format!("Successfully repaired {} unit problems", problems.len()));
+
+    Ok(report.join("\n"))
+}
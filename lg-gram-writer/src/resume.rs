@@ -0,0 +1,72 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use glob::glob;
+
+use crate::{WRITER, FEATURES};
+use crate::overrides;
+use crate::persistence::{self, UNIT_PATH, ENABLED_PATH, run_command, exists, systemd_running};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+pub const RESUME_SERVICE: &str = "lg_gram_resume.service";
+
+const ELOGIND_PATH: &str = "/etc/elogind/system-sleep";
+const ELOGIND_HOOK: &str = "/etc/elogind/system-sleep/lg-gram-settings";
+
+//---------------------------------------
+// Sync resume hook function
+//---------------------------------------
+pub fn sync_hook() -> Result<(), String> {
+    // Resume hook is only needed if any setting is persisted or overridden
+    let needed = FEATURES.iter()
+        .any(|feature| persistence::persisted(feature.id).is_some() || overrides::get(feature.id).is_some());
+
+    sync_systemd_hook(needed)?;
+    sync_elogind_hook(needed)
+}
+
+//---------------------------------------
+// Sync systemd hook helper function
+//---------------------------------------
+fn sync_systemd_hook(needed: bool) -> Result<(), String> {
+    if !systemd_running() || !exists(&format!("{UNIT_PATH}/{RESUME_SERVICE}")) {
+        return Ok(())
+    }
+
+    let enabled = glob(&format!("{ENABLED_PATH}/**/{RESUME_SERVICE}"))
+        .expect("Failed to read glob pattern")
+        .flatten()
+        .next()
+        .is_some();
+
+    match (needed, enabled) {
+        (true, false) => run_command("systemctl", &["enable", RESUME_SERVICE]),
+        (false, true) => run_command("systemctl", &["disable", RESUME_SERVICE]),
+        _ => Ok(())
+    }
+}
+
+//---------------------------------------
+// Sync elogind hook helper function
+//---------------------------------------
+fn sync_elogind_hook(needed: bool) -> Result<(), String> {
+    if needed {
+        if !exists("/etc/elogind") {
+            return Ok(())
+        }
+
+        let content = format!("#!/bin/sh\n# Managed by lg-gram-writer\n[ \"$1\" = \"post\" ] && {WRITER} reapply\nexit 0\n");
+
+        fs::create_dir_all(ELOGIND_PATH)
+            .and_then(|()| fs::write(ELOGIND_HOOK, content))
+            .and_then(|()| fs::set_permissions(ELOGIND_HOOK, fs::Permissions::from_mode(0o755)))
+            .map_err(|_| format!("ERROR: Error writing to {ELOGIND_HOOK}"))
+    } else if exists(ELOGIND_HOOK) {
+        fs::remove_file(ELOGIND_HOOK)
+            .map_err(|_| format!("ERROR: Error removing {ELOGIND_HOOK}"))
+    } else {
+        Ok(())
+    }
+}
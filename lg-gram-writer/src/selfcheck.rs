@@ -0,0 +1,206 @@
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::{WRITER, SETTINGS_PATH, MODULE_PATH, FEATURES};
+use crate::persistence::{UNIT_PATH, exists, systemd_running};
+use crate::resume::RESUME_SERVICE;
+use crate::watch::WATCH_SERVICE;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const POLKIT_POLICY_FILE: &str = "/usr/share/polkit-1/actions/lg.gram.writer.policy";
+
+//------------------------------------------------------------------------------
+// TYPES
+//------------------------------------------------------------------------------
+// Check result: Ok(detail) or Err((problem, fix))
+type CheckResult = Result<String, (String, String)>;
+
+//---------------------------------------
+// Writer check function
+//---------------------------------------
+fn check_writer() -> CheckResult {
+    let metadata = fs::metadata(WRITER)
+        .map_err(|_| (format!("{WRITER} not found"), format!("Install lg-gram-writer to {WRITER}")))?;
+
+    if metadata.uid() != 0 || metadata.gid() != 0 {
+        return Err((
+            format!("{WRITER} is not owned by root"),
+            format!("Run: chown root:root {WRITER}")
+        ))
+    }
+
+    if metadata.mode() & 0o022 != 0 {
+        return Err((
+            format!("{WRITER} is writable by group or others"),
+            format!("Run: chmod 755 {WRITER}")
+        ))
+    }
+
+    if metadata.mode() & 0o111 == 0 {
+        return Err((
+            format!("{WRITER} is not executable"),
+            format!("Run: chmod 755 {WRITER}")
+        ))
+    }
+
+    // GUI refuses to elevate writer if any parent directory can be changed by users
+    for dir in Path::new(WRITER).ancestors().skip(1) {
+        let metadata = fs::symlink_metadata(dir)
+            .map_err(|_| (format!("{} not found", dir.display()), format!("Install lg-gram-writer to {WRITER}")))?;
+
+        if metadata.uid() != 0 {
+            return Err((
+                format!("{} is not owned by root", dir.display()),
+                format!("Run: chown root:root {}", dir.display())
+            ))
+        }
+
+        if !metadata.is_symlink() && metadata.mode() & 0o022 != 0 {
+            return Err((
+                format!("{} is writable by group or others", dir.display()),
+                format!("Run: chmod go-w {}", dir.display())
+            ))
+        }
+    }
+
+    Ok(format!("{WRITER} is installed and owned by root"))
+}
+
+//---------------------------------------
+// Policy check function
+//---------------------------------------
+fn check_policy() -> CheckResult {
+    let fix = String::from("Install lg.gram.writer.policy to /usr/share/polkit-1/actions");
+
+    let policy = fs::read_to_string(POLKIT_POLICY_FILE)
+        .map_err(|_| (format!("{POLKIT_POLICY_FILE} not found"), fix.clone()))?;
+
+    let annotation = format!("<annotate key=\"org.freedesktop.policykit.exec.path\">{WRITER}</annotate>");
+
+    if !policy.contains(&annotation) {
+        return Err((format!("Policy exec path does not match {WRITER}"), fix))
+    }
+
+    // Actions checked by the writer for each operation
+    let missing = FEATURES.iter()
+        .map(|feature| feature.action)
+        .find(|action| !policy.contains(&format!("<action id=\"{action}\">")));
+
+    if let Some(action) = missing {
+        return Err((format!("Policy is outdated, action {action} is missing"), fix))
+    }
+
+    Ok(format!("{POLKIT_POLICY_FILE} is installed"))
+}
+
+//---------------------------------------
+// Units check function
+//---------------------------------------
+fn check_units() -> CheckResult {
+    if !systemd_running() {
+        return Ok(String::from("Not running systemd, unit files not required"))
+    }
+
+    let missing: Vec<String> = FEATURES.iter()
+        .flat_map(|feature| {
+            feature.values.iter()
+                .filter(|(value, _)| *value != feature.default)
+                .map(|(value, _)| format!("lg_gram_{}_{value}.service", feature.id))
+        })
+        .chain([String::from(RESUME_SERVICE), String::from(WATCH_SERVICE)])
+        .filter(|service| !exists(&format!("{UNIT_PATH}/{service}")))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err((
+            format!("Missing unit files: {}", missing.join(", ")),
+            format!("Install the lg_gram_*.service files to {UNIT_PATH}")
+        ))
+    }
+
+    Ok(format!("All unit files are installed in {UNIT_PATH}"))
+}
+
+//---------------------------------------
+// Pkexec check function
+//---------------------------------------
+fn check_pkexec() -> CheckResult {
+    let pkexec = env::var("PATH").unwrap_or_default()
+        .split(':')
+        .map(|dir| Path::new(dir).join("pkexec"))
+        .find(|path| path.exists())
+        .ok_or_else(|| (String::from("pkexec not found"), String::from("Install polkit")))?;
+
+    let metadata = fs::metadata(&pkexec)
+        .map_err(|error| (error.to_string(), String::from("Reinstall polkit")))?;
+
+    if metadata.uid() != 0 || metadata.mode() & 0o4000 == 0 {
+        return Err((
+            format!("{} is not setuid root", pkexec.display()),
+            String::from("Reinstall polkit")
+        ))
+    }
+
+    Ok(format!("{} is available", pkexec.display()))
+}
+
+//---------------------------------------
+// Driver check function
+//---------------------------------------
+fn check_driver() -> CheckResult {
+    if !exists(MODULE_PATH) {
+        return Err((
+            String::from("lg_laptop kernel module is not loaded"),
+            String::from("Run: modprobe lg_laptop (requires a supported LG Gram laptop)")
+        ))
+    }
+
+    if !exists(SETTINGS_PATH) {
+        return Err((
+            format!("{SETTINGS_PATH} not found"),
+            String::from("Check kernel log for lg_laptop errors (laptop model may not be supported)")
+        ))
+    }
+
+    Ok(String::from("lg_laptop kernel module is loaded"))
+}
+
+//---------------------------------------
+// Self check function
+//---------------------------------------
+pub fn self_check() -> Result<String, String> {
+    let checks = [
+        ("Writer", check_writer()),
+        ("Polkit Policy", check_policy()),
+        ("Unit Files", check_units()),
+        ("Pkexec", check_pkexec()),
+        ("Driver", check_driver()),
+    ];
+
+    let failed = checks.iter()
+        .filter(|(_, result)| result.is_err())
+        .count();
+
+    let report = checks.into_iter()
+        .map(|(name, result)| {
+            match result {
+                Ok(detail) => format!("PASS {name}: {detail}"),
+                Err((problem, fix)) => format!("FAIL {name}: {problem}\n  FIX {fix}")
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    // Print report, fail if any check failed
+    println!("{report}");
+
+    if failed > 0 {
+        return Err(format!("ERROR: {failed} installation checks failed"))
+    }
+
+    Ok(String::new())
+}
@@ -0,0 +1,69 @@
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::authorize;
+use crate::cli::{self, Mode};
+use crate::error::Error;
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+//---------------------------------------
+// Allowed request helper function
+//---------------------------------------
+fn is_allowed(mode: &Mode) -> bool {
+    // Exclude long-running modes and modes that print to stdout directly
+    !matches!(mode, Mode::Session | Mode::Watch | Mode::SelfCheck | Mode::Repair { check: true }
+        | Mode::Diagnostics)
+}
+
+//---------------------------------------
+// Session function
+//---------------------------------------
+// Each request is one line of writer arguments (e.g. "set fn_lock 1"), each response is a
+// "OK <length>" or "ERROR <exit code> <length>" line followed by <length> bytes of output
+pub fn session() -> Result<String, String> {
+    authorize::start_session();
+
+    let (sender, receiver) = mpsc::channel();
+
+    // Read requests in background, so that idle timeout can be applied
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            if line.map(|line| sender.send(line)).is_err() {
+                break
+            }
+        }
+    });
+
+    let mut stdout = io::stdout().lock();
+
+    // Exit when stdin is closed (window closed) or after idle timeout
+    while let Ok(request) = receiver.recv_timeout(IDLE_TIMEOUT) {
+        let result = cli::try_parse_from(request.split_whitespace())
+            .map_err(Error::from)
+            .and_then(|mode| {
+                if !is_allowed(&mode) {
+                    return Err(format!("ERROR: Request '{request}' is not supported in session").into())
+                }
+
+                crate::execute(mode)
+            });
+
+        let (status, output) = match result {
+            Ok(output) => (String::from("OK"), output),
+            Err(error) => (format!("ERROR {}", error.code()), error.message)
+        };
+
+        writeln!(stdout, "{status} {}", output.len())
+            .and_then(|()| stdout.write_all(output.as_bytes()))
+            .and_then(|()| stdout.flush())
+            .map_err(|error| format!("ERROR: Failed to write response: {error}"))?;
+    }
+
+    Ok(String::new())
+}
@@ -0,0 +1,206 @@
+use std::fs;
+use std::iter;
+use std::path::Path;
+
+use crate::{MODULE_PATH, POWER_SUPPLY_PATH};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+const DMI_PATH: &str = "/sys/devices/virtual/dmi/id";
+
+//------------------------------------------------------------------------------
+// TYPES
+//------------------------------------------------------------------------------
+type Fields = Vec<(&'static str, String)>;
+
+//---------------------------------------
+// Read value helper function
+//---------------------------------------
+fn read_value(file: &str) -> Option<String> {
+    fs::read_to_string(file).ok()
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+//---------------------------------------
+// DMI read helper function
+//---------------------------------------
+fn dmi_read(param: &str) -> Result<String, String> {
+    let file = format!("{DMI_PATH}/{param}");
+
+    fs::read_to_string(file)
+        .map_err(|error| error.to_string())
+        .map(|value| value.trim().to_owned())
+}
+
+//---------------------------------------
+// Mask helper function
+//---------------------------------------
+pub fn mask(value: &str) -> String {
+    // Keep last 4 characters for identification of long values
+    let count = value.chars().count();
+    let keep = if count > 8 { 4 } else { 0 };
+
+    value.chars()
+        .enumerate()
+        .map(|(i, c)| if i < count - keep && c.is_alphanumeric() { '*' } else { c })
+        .collect()
+}
+
+//---------------------------------------
+// System fields function
+//---------------------------------------
+fn system_fields(reveal: bool) -> Result<Fields, String> {
+    let identify = |value: String| if reveal { value } else { mask(&value) };
+
+    let mut fields = vec![
+        ("System Vendor", dmi_read("sys_vendor")?),
+        ("Product Family", dmi_read("product_family")?),
+        ("Product Name", dmi_read("product_name")?),
+    ];
+
+    if let Some(board_name) = read_value(&format!("{DMI_PATH}/board_name")) {
+        fields.push(("Board Name", board_name));
+    }
+
+    // Identifying fields are masked unless revealed
+    fields.push(("Serial Number", identify(dmi_read("product_serial")?)));
+
+    if let Some(uuid) = read_value(&format!("{DMI_PATH}/product_uuid")) {
+        fields.push(("UUID", identify(uuid)));
+    }
+
+    if let Some(asset_tag) = read_value(&format!("{DMI_PATH}/chassis_asset_tag")) {
+        fields.push(("Asset Tag", identify(asset_tag)));
+    }
+
+    Ok(fields)
+}
+
+//---------------------------------------
+// BIOS fields function
+//---------------------------------------
+fn bios_fields() -> Result<Fields, String> {
+    let mut fields = vec![
+        ("BIOS Vendor", dmi_read("bios_vendor")?),
+        ("BIOS Version", dmi_read("bios_version")?),
+        ("BIOS Date", dmi_read("bios_date")?),
+    ];
+
+    if let Some(bios_release) = read_value(&format!("{DMI_PATH}/bios_release")) {
+        fields.push(("BIOS Release", bios_release));
+    }
+
+    if let Some(ec_release) = read_value(&format!("{DMI_PATH}/ec_firmware_release")) {
+        fields.push(("EC Firmware Release", ec_release));
+    }
+
+    Ok(fields)
+}
+
+//---------------------------------------
+// Battery fields function
+//---------------------------------------
+fn battery_fields() -> Fields {
+    // Find first battery power supply
+    let Some(battery) = fs::read_dir(POWER_SUPPLY_PATH).into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .find(|path| read_value(&format!("{path}/type")).as_deref() == Some("Battery")) else {
+            return vec![]
+        };
+
+    let read = |param: &str| read_value(&format!("{battery}/{param}"));
+
+    // Capacity is either reported as energy (µWh) or charge (µAh)
+    let capacity = |param: &str| {
+        read(&format!("energy_{param}"))
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(|value| (value, "Wh"))
+            .or_else(|| {
+                read(&format!("charge_{param}"))
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .map(|value| (value, "Ah"))
+            })
+    };
+
+    let mut fields = vec![];
+
+    for (label, param) in [("Manufacturer", "manufacturer"), ("Model", "model_name"), ("Technology", "technology")] {
+        if let Some(value) = read(param) {
+            fields.push((label, value));
+        }
+    }
+
+    let design = capacity("full_design");
+    let full = capacity("full");
+
+    if let Some((value, unit)) = design {
+        fields.push(("Design Capacity", format!("{:.1} {unit}", value as f64 / 1_000_000.0)));
+    }
+
+    if let Some((value, unit)) = full {
+        let health = design
+            .filter(|&(design, design_unit)| design > 0 && design_unit == unit)
+            .map(|(design, _)| format!(" ({}%)", value * 100 / design))
+            .unwrap_or_default();
+
+        fields.push(("Full Charge Capacity", format!("{:.1} {unit}{health}", value as f64 / 1_000_000.0)));
+    }
+
+    if let Some(cycle_count) = read("cycle_count") {
+        fields.push(("Cycle Count", cycle_count));
+    }
+
+    fields
+}
+
+//---------------------------------------
+// Driver fields function
+//---------------------------------------
+fn driver_fields() -> Fields {
+    let mut fields = vec![];
+
+    if let Some(kernel_release) = read_value("/proc/sys/kernel/osrelease") {
+        fields.push(("Kernel Release", kernel_release));
+    }
+
+    let loaded = Path::new(MODULE_PATH).exists();
+
+    fields.push(("Driver Loaded", String::from(if loaded { "Yes" } else { "No" })));
+
+    if let Some(version) = read_value(&format!("{MODULE_PATH}/version")) {
+        fields.push(("Driver Version", version));
+    }
+
+    if let Some(srcversion) = read_value(&format!("{MODULE_PATH}/srcversion")) {
+        fields.push(("Driver Source Version", srcversion));
+    }
+
+    fields
+}
+
+//---------------------------------------
+// System information function
+//---------------------------------------
+pub fn system_information(reveal: bool) -> Result<String, String> {
+    let sections = [
+        ("System", system_fields(reveal)?),
+        ("BIOS", bios_fields()?),
+        ("Battery", battery_fields()),
+        ("Driver", driver_fields()),
+    ];
+
+    let output = sections.into_iter()
+        .filter(|(_, fields)| !fields.is_empty())
+        .flat_map(|(title, fields)| {
+            iter::once(format!("[{title}]"))
+                .chain(fields.into_iter().flat_map(|(label, value)| [label.to_owned(), value]))
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    Ok(output)
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::features::{Feature, FEATURES};
+use crate::overrides;
+use crate::persistence::{UNIT_PATH, run_command, exists, systemd_running};
+
+//------------------------------------------------------------------------------
+// CONSTANTS
+//------------------------------------------------------------------------------
+pub const WATCH_SERVICE: &str = "lg_gram_watch.service";
+
+const CONFIG_PATH: &str = "/etc/lg-gram-settings";
+const ENFORCE_FILE: &str = "/etc/lg-gram-settings/enforce";
+
+// lg-laptop attributes do not notify changes, so values are also polled
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+// Firmware changes settings shortly after a power supply event
+const SETTLE_DELAY: Duration = Duration::from_secs(1);
+
+//---------------------------------------
+// Enforced features function
+//---------------------------------------
+pub fn enforced() -> Vec<&'static Feature> {
+    let content = fs::read_to_string(ENFORCE_FILE).unwrap_or_default();
+
+    FEATURES.iter()
+        .filter(|feature| content.lines().any(|line| line.trim() == feature.id))
+        .collect()
+}
+
+//---------------------------------------
+// Set enforced function
+//---------------------------------------
+pub fn set_enforced(feature: &Feature, enabled: bool) -> Result<String, String> {
+    let current = enforced();
+
+    let ids: Vec<&str> = FEATURES.iter()
+        .filter(|other| {
+            if other.id == feature.id {
+                enabled
+            } else {
+                current.iter().any(|enforced| enforced.id == other.id)
+            }
+        })
+        .map(|feature| feature.id)
+        .collect();
+
+    if ids.is_empty() {
+        if exists(ENFORCE_FILE) {
+            fs::remove_file(ENFORCE_FILE)
+                .map_err(|_| format!("ERROR: Error removing {ENFORCE_FILE}"))?;
+        }
+    } else {
+        fs::create_dir_all(CONFIG_PATH)
+            .and_then(|()| fs::write(ENFORCE_FILE, format!("# Managed by lg-gram-writer\n{}\n", ids.join("\n"))))
+            .map_err(|_| format!("ERROR: Error writing to {ENFORCE_FILE}"))?;
+    }
+
+    // Start watcher service if any feature is enforced
+    let message = sync_service(!ids.is_empty())?;
+
+    let state = if enabled { "Enabled" } else { "Disabled" };
+
+    Ok(format!("{state} enforcement of {} setting{message}", feature.id))
+}
+
+//---------------------------------------
+// Sync service helper function
+//---------------------------------------
+fn sync_service(needed: bool) -> Result<String, String> {
+    if !systemd_running() || !exists(&format!("{UNIT_PATH}/{WATCH_SERVICE}")) {
+        return Ok(if needed {
+            String::from(" (run 'lg-gram-writer watch' as a service to enforce settings)")
+        } else {
+            String::new()
+        })
+    }
+
+    if needed {
+        run_command("systemctl", &["enable", "--now", WATCH_SERVICE])?;
+    } else {
+        run_command("systemctl", &["disable", "--now", WATCH_SERVICE])?;
+    }
+
+    Ok(String::new())
+}
+
+//---------------------------------------
+// Uevent monitor helper function
+//---------------------------------------
+fn spawn_uevent_monitor(sender: Sender<()>) {
+    thread::spawn(move || {
+        let child = process::Command::new("udevadm")
+            .args(["monitor", "--kernel", "--subsystem-match=power_supply"])
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::null())
+            .spawn();
+
+        let Some(stdout) = child.ok().and_then(|mut child| child.stdout.take()) else {
+            eprintln!("WARNING: Failed to monitor power supply events, polling only");
+            return
+        };
+
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if line.starts_with("KERNEL[") && sender.send(()).is_err() {
+                break
+            }
+        }
+    });
+}
+
+//---------------------------------------
+// Enforce features helper function
+//---------------------------------------
+fn enforce_features() {
+    for feature in enforced() {
+        let Ok(current) = feature.read() else {
+            continue
+        };
+
+        let target = match overrides::effective(feature) {
+            Ok(value) => value.unwrap_or_else(|| feature.default.to_owned()),
+            Err(error) => {
+                eprintln!("{error}");
+                continue
+            }
+        };
+
+        if current != target {
+            match feature.write(&target) {
+                Ok(()) => println!("Restored {} setting to {target} (was changed to {current})", feature.id),
+                Err(error) => eprintln!("{error}")
+            }
+        }
+    }
+}
+
+//---------------------------------------
+// Watch function
+//---------------------------------------
+pub fn watch() -> Result<String, String> {
+    let (sender, receiver) = mpsc::channel();
+
+    // AC plugged or unplugged
+    spawn_uevent_monitor(sender);
+
+    println!("Watching enforced settings: {}", enforced().iter()
+        .map(|feature| feature.id)
+        .collect::<Vec<&str>>()
+        .join(", ")
+    );
+
+    loop {
+        enforce_features();
+
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(()) => {
+                thread::sleep(SETTLE_DELAY);
+
+                // Coalesce events for the same change
+                while receiver.try_recv().is_ok() {}
+            },
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(POLL_INTERVAL)
+        }
+    }
+}